@@ -0,0 +1,82 @@
+//! Compares `Exec` against the bytecode `Vm` on fully optimized programs.
+//!
+//! Runs `programs/mandelbrot.b`, which has to be provided separately, then
+//! `benches/divmod.b` on a generated input. Other programs can be passed as
+//! arguments instead.
+
+use rustfck::{
+    frontend::{code_gen::gen_program, lexer::lex, optimize::apply_optimizations, parser::parse},
+    ir::{bytecode::lower, exec::Exec, optimize::optimize_module, vm::Vm, Module},
+};
+use std::{
+    env::args,
+    fs,
+    io::{sink, Cursor},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const RUNS: usize = 5;
+
+fn compile(path: &Path) -> Module {
+    let src = fs::read_to_string(path).unwrap();
+    let mut program = parse(lex(Cursor::new(src))).gen_expr_tree();
+    apply_optimizations(&mut program);
+    let mut module = gen_program(&program);
+    optimize_module(&mut module);
+    module
+}
+
+fn best_of(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn bench(path: &Path, input: &[u8]) {
+    let module = compile(path);
+
+    let exec = best_of(|| {
+        Exec::new(sink(), input).exec_program(&module).unwrap();
+    });
+    let lowering = best_of(|| {
+        lower(&module).unwrap();
+    });
+    let code = lower(&module).unwrap();
+    let vm = best_of(|| {
+        Vm::new(sink(), input).exec(&code).unwrap();
+    });
+
+    println!("{}", path.display());
+    println!("  exec  {exec:>12.3?}");
+    println!(
+        "  vm    {vm:>12.3?}  (+{lowering:.3?} lowering, {} ops)",
+        code.ops.len()
+    );
+    println!("  speedup {:.2}x", exec.as_secs_f64() / vm.as_secs_f64());
+}
+
+fn main() {
+    let paths: Vec<String> = args().skip(1).filter(|a| !a.starts_with('-')).collect();
+    if !paths.is_empty() {
+        paths.iter().for_each(|p| bench(Path::new(p), b""));
+        return;
+    }
+
+    let mandelbrot = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs/mandelbrot.b");
+    assert!(
+        mandelbrot.exists(),
+        "{} is missing; put Erik Bosman's mandelbrot.b there or pass programs as arguments",
+        mandelbrot.display()
+    );
+    bench(&mandelbrot, b"");
+
+    let input: Vec<u8> = (0..20_000u32).map(|i| (i * 7919 % 255 + 1) as u8).collect();
+    let divmod = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/divmod.b");
+    bench(&divmod, &input);
+}
@@ -1,7 +1,15 @@
 pub mod ast;
 pub mod code_gen;
+pub mod debugger;
+pub mod emit;
+pub mod exec;
 pub mod expr_tree;
+pub mod format;
 pub mod lexer;
+pub mod lint;
 pub mod optimize;
 pub mod parser;
+pub mod partial_eval;
 pub mod printing;
+pub mod profile;
+pub mod reference;
@@ -1,5 +1,7 @@
 use super::expr_tree::{BoundsRange, Instruction, Program};
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ast(pub Vec<AstNode>);
 impl Ast {
     pub fn gen_expr_tree(&self) -> Program {
@@ -7,8 +9,21 @@ impl Ast {
         self.0.iter().for_each(|i| i.gen_expr_tree(&mut body));
         Program(body)
     }
+
+    /// The input embedded after a `!`, if the program has any.
+    ///
+    /// [`AstNode::Data`] can only be the last node of the top level, as a `!`
+    /// inside a loop fails to parse.
+    pub fn input(&self) -> Option<&[u8]> {
+        match self.0.last() {
+            Some(AstNode::Data(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AstNode {
     Modify(i8),
     Move(isize),
@@ -16,9 +31,15 @@ pub enum AstNode {
     Input,
     Set(u8),
     Loop(Vec<AstNode>),
+    Dump,
+    /// Everything after a `!`; only ever the last top-level node.
+    Data(Vec<u8>),
 }
 impl AstNode {
     fn gen_expr_tree(&self, instructions: &mut Vec<Instruction>) {
+        if let Self::Data(_) = self {
+            return;
+        }
         if self.accesses_cell() {
             instructions.push(Instruction::BoundsCheck(BoundsRange {
                 start: 0,
@@ -41,6 +62,8 @@ impl AstNode {
                 }));
                 Instruction::Loop(false, 0, new_body)
             }
+            Self::Dump => Instruction::Dump(0),
+            Self::Data(_) => unreachable!(),
         });
     }
 
@@ -53,6 +76,8 @@ impl AstNode {
             Input => true,
             Set(_) => true,
             Loop(_) => true,
+            Dump => false,
+            Data(_) => false,
         }
     }
 }
@@ -3,7 +3,10 @@ use std::{
     mem::take,
 };
 
-use super::expr_tree::{CellOffset, Instruction, Program};
+use super::{
+    expr_tree::{CellOffset, Instruction, Program},
+    profile::{LoopInfo, LoopMap},
+};
 use crate::ir::{
     block::BlockID,
     builder::Builder,
@@ -14,11 +17,15 @@ use crate::ir::{
 };
 
 pub fn gen_program(program: &Program) -> Module {
+    gen_program_with_loops(program).0
+}
+/// Like [`gen_program`], but also records which loop each block belongs to.
+pub fn gen_program_with_loops(program: &Program) -> (Module, LoopMap) {
     let mut module = Module::new();
     let code_gen = CodeGen::new(&mut module);
-    code_gen.gen_program(program);
+    let loops = code_gen.gen_program(program);
 
-    module
+    (module, loops)
 }
 
 pub struct CodeGen<'a> {
@@ -28,6 +35,10 @@ pub struct CodeGen<'a> {
     indices: HashMap<CellOffset, RegisterID>,
     cells: HashMap<CellOffset, RegisterID>,
     written: HashSet<CellOffset>,
+
+    path: Vec<usize>,
+    loop_stack: Vec<usize>,
+    loops: LoopMap,
 }
 impl<'a> CodeGen<'a> {
     fn new(module: &'a mut Module) -> Self {
@@ -41,34 +52,58 @@ impl<'a> CodeGen<'a> {
             indices: HashMap::new(),
             cells: HashMap::new(),
             written: HashSet::new(),
+            path: Vec::new(),
+            loop_stack: Vec::new(),
+            loops: LoopMap {
+                loops: Vec::new(),
+                blocks: vec![None],
+            },
         }
     }
 
-    fn gen_program(mut self, program: &Program) {
-        for i in &program.0 {
+    fn gen_program(mut self, program: &Program) -> LoopMap {
+        self.gen_body(&program.0);
+        self.spill_values();
+        self.loops
+    }
+    fn gen_body(&mut self, instructions: &[Instruction]) {
+        self.path.push(0);
+        for (index, i) in instructions.iter().enumerate() {
+            *self.path.last_mut().unwrap() = index;
             self.gen_instruction(i);
         }
+        self.path.pop();
     }
     fn gen_instruction(&mut self, instruction: &Instruction) {
         use Instruction::*;
-        match instruction {
-            &Modify(offset, amount) => {
+        match *instruction {
+            Modify(offset, amount) => {
                 let old = self.get_cell(offset);
                 let new = self.builder.add(old, amount);
                 self.set_cell(offset, new);
             }
-            &Move(amount) => self.move_index(amount),
-            &Output(cell) => {
+            Move(amount) => self.move_index(amount),
+            Output(cell) => {
                 let val = self.get_cell(cell);
                 self.builder.output(val);
             }
-            &Input(cell) => {
+            Input(cell) => {
                 let old = self.get_cell(cell);
                 let read = self.builder.input(old);
                 self.set_cell(cell, read);
             }
-            &Set(cell, val) => self.set_cell(cell, val),
-            &AddMultiple {
+            Set(cell, val) => self.set_cell(cell, val),
+            Print(ref bytes) => {
+                for &byte in bytes {
+                    self.builder.output(byte);
+                }
+            }
+            Dump(cell) => {
+                self.spill_values();
+                let index = self.get_cell_index(cell);
+                self.builder.dump(index);
+            }
+            AddMultiple {
                 target,
                 base,
                 factor,
@@ -79,20 +114,38 @@ impl<'a> CodeGen<'a> {
                 let total = self.builder.add(target_val, addend);
                 self.set_cell(target, total);
             }
-            &BoundsCheck(bounds) => {
+            Copy {
+                target,
+                base,
+                factor,
+            } => {
+                let base_val = self.get_cell(base);
+                let value = self.builder.mul(base_val, factor);
+                self.set_cell(target, value);
+            }
+            BoundsCheck(bounds) => {
                 let start = self.builder.add(self.index, bounds.start as i64);
                 let end = self.builder.add(start, bounds.length as u64);
                 self.builder.check_bounds(start, end);
             }
-            &Loop(balanced, condition, ref body) => self.gen_loop(!balanced, condition, body),
-            &If(balanced, condition, ref body) => self.gen_if(!balanced, condition, body),
+            Loop(balanced, condition, ref body) => self.gen_loop(!balanced, condition, body),
+            If(balanced, condition, ref body) => self.gen_if(!balanced, condition, body),
         }
     }
 
     fn gen_loop(&mut self, unbalanced: bool, condition: CellOffset, instructions: &[Instruction]) {
-        let header = self.builder.add_block();
-        let body = self.builder.add_block();
-        let end = self.builder.add_block();
+        let id = self.loops.loops.len();
+        self.loop_stack.push(id);
+        let header = self.add_block();
+        let body = self.add_block();
+        self.loop_stack.pop();
+        let end = self.add_block();
+        self.loops.loops.push(LoopInfo {
+            path: self.path.clone(),
+            parent: self.loop_stack.last().copied(),
+            condition,
+            body,
+        });
 
         self.jump_to(header, unbalanced);
         let context = self.save_context();
@@ -103,17 +156,17 @@ impl<'a> CodeGen<'a> {
         self.branch_to(not_zero, body, end, unbalanced);
 
         self.enter_branch(body, false);
-        for i in instructions {
-            self.gen_instruction(i);
-        }
+        self.loop_stack.push(id);
+        self.gen_body(instructions);
+        self.loop_stack.pop();
         self.jump_to(header, unbalanced);
 
         self.enter_branch(end, unbalanced);
         self.restore_context(context);
     }
     fn gen_if(&mut self, unbalanced: bool, condition: CellOffset, instructions: &[Instruction]) {
-        let body = self.builder.add_block();
-        let end = self.builder.add_block();
+        let body = self.add_block();
+        let end = self.add_block();
 
         let cell_val = self.get_cell(condition);
         let not_zero = self.builder.test(TestOp::NotEqual, cell_val, 0i8);
@@ -121,15 +174,19 @@ impl<'a> CodeGen<'a> {
         let context = self.save_context();
 
         self.enter_branch(body, false);
-        for i in instructions {
-            self.gen_instruction(i);
-        }
+        self.gen_body(instructions);
         self.jump_to(end, unbalanced);
 
         self.enter_branch(end, unbalanced);
         self.restore_context(context);
     }
 
+    fn add_block(&mut self) -> BlockID {
+        let block = self.builder.add_block();
+        self.loops.blocks.push(self.loop_stack.last().copied());
+        block
+    }
+
     fn branch_to(&mut self, c: impl Into<LeafExpr>, then: BlockID, els: BlockID, unbalanced: bool) {
         if unbalanced {
             self.spill_values();
@@ -0,0 +1,398 @@
+use super::{
+    exec::TreeExec,
+    expr_tree::{BoundsRange, Instruction, Program},
+    lexer::{Span, Token},
+};
+use crate::ir::limits::ExecError;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Cursor,
+    iter::Peekable,
+};
+
+pub mod repl;
+
+/// How many steps a command runs before giving control back, unless the
+/// debugger was given a different limit.
+pub const STEP_LIMIT: u64 = 1 << 24;
+
+/// Steps through an unoptimized expr_tree, one instruction per run of source
+/// characters, and maps every position back to its span in the source.
+///
+/// The debugger only keeps track of where it is; every instruction runs on a
+/// [`TreeExec`].
+pub struct Debugger {
+    program: Program,
+    spans: Vec<SpanNode>,
+    frames: Vec<usize>,
+    exec: TreeExec<Vec<u8>, Cursor<Vec<u8>>>,
+    steps: u64,
+    step_limit: Option<u64>,
+    shown: usize,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, u8>,
+}
+
+struct SpanNode {
+    span: Span,
+    /// The spans of a loop's body.
+    body: Option<Vec<SpanNode>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Done,
+    Halted,
+    Breakpoint(usize),
+    Watchpoint {
+        cell: usize,
+        old: u8,
+        new: u8,
+    },
+    NegativePointer,
+    /// The command ran for its whole step limit.
+    StepLimit,
+    Failed(String),
+}
+impl From<ExecError> for Stop {
+    fn from(err: ExecError) -> Self {
+        match err {
+            ExecError::LeftOfTape(_) => Self::NegativePointer,
+            err => Self::Failed(err.to_string()),
+        }
+    }
+}
+
+/// What the debugger is about to execute.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Location {
+    Instruction(Span),
+    /// The `]` of a loop whose body just finished.
+    LoopEnd(Span),
+    Halted,
+}
+impl Location {
+    pub fn span(self) -> Option<Span> {
+        match self {
+            Self::Instruction(span) | Self::LoopEnd(span) => Some(span),
+            Self::Halted => None,
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new(
+        tokens: impl Iterator<Item = (Token, Span)>,
+        input: Vec<u8>,
+    ) -> Result<Self, String> {
+        let mut tokens = tokens.peekable();
+        let (body, spans, close) = build(&mut tokens)?;
+        if let Some(close) = close {
+            return Err(format!("unmatched ] at offset {}", close.start));
+        }
+
+        Ok(Self {
+            program: Program(body),
+            spans,
+            frames: vec![0],
+            exec: TreeExec::new(Vec::new(), Cursor::new(input)),
+            steps: 0,
+            step_limit: Some(STEP_LIMIT),
+            shown: 0,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+        })
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+    pub fn pointer(&self) -> isize {
+        self.exec.pointer()
+    }
+    pub fn cell(&self, index: usize) -> u8 {
+        self.exec.cells().get(index).copied().unwrap_or(0)
+    }
+    /// The cells `radius` either side of the pointer, and the index of the first one.
+    pub fn tape_around_pointer(&self, radius: usize) -> (usize, Vec<u8>) {
+        let start = (self.pointer().max(0) as usize).saturating_sub(radius);
+        let end = self.pointer().max(0) as usize + radius + 1;
+        (start, (start..end).map(|i| self.cell(i)).collect())
+    }
+
+    pub fn output(&self) -> &[u8] {
+        self.exec.stdout()
+    }
+    /// Output written since the last call.
+    pub fn take_pending_output(&mut self) -> &[u8] {
+        let shown = self.shown;
+        self.shown = self.exec.stdout().len();
+        &self.exec.stdout()[shown..]
+    }
+
+    /// Limits how many steps a single command may run, so that an infinite
+    /// loop hands control back instead of hanging the session.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    pub fn add_breakpoint(&mut self, offset: usize) -> bool {
+        let exists = self.instruction_heads().any(|span| span.contains(offset));
+        if exists {
+            self.breakpoints.insert(offset);
+        }
+        exists
+    }
+    pub fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.remove(&offset)
+    }
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn watch(&mut self, cell: usize) {
+        self.watchpoints.insert(cell, self.cell(cell));
+    }
+    pub fn unwatch(&mut self, cell: usize) -> bool {
+        self.watchpoints.remove(&cell).is_some()
+    }
+    pub fn watchpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watchpoints.keys().copied()
+    }
+
+    pub fn location(&self) -> Location {
+        let depth = self.frames.len() - 1;
+        let index = self.frames[depth];
+        match self.spans(depth).get(index) {
+            Some(node) => Location::Instruction(head(node)),
+            None if depth == 0 => Location::Halted,
+            None => {
+                let parent = self.spans(depth - 1);
+                let loop_span = parent[self.frames[depth - 1]].span;
+                Location::LoopEnd(Span::at(loop_span.end - 1))
+            }
+        }
+    }
+
+    /// Executes one instruction, entering loops.
+    pub fn step(&mut self) -> Stop {
+        self.run_while(|_| false)
+    }
+    /// Executes one instruction, running loops to completion.
+    pub fn step_over(&mut self) -> Stop {
+        let depth = self.frames.len();
+        self.run_while(|frames| frames.len() > depth)
+    }
+    /// Runs until the innermost loop is left.
+    pub fn finish(&mut self) -> Stop {
+        let depth = self.frames.len();
+        self.run_while(|frames| depth == 1 || frames.len() >= depth)
+    }
+    pub fn resume(&mut self) -> Stop {
+        self.run_while(|_| true)
+    }
+    /// Like [`Debugger::resume`], but stops after at most `steps` steps.
+    pub fn resume_for(&mut self, steps: u64) -> Stop {
+        let limit = self.step_limit.replace(steps);
+        let stop = self.resume();
+        self.step_limit = limit;
+        stop
+    }
+
+    fn run_while(&mut self, mut keep_going: impl FnMut(&[usize]) -> bool) -> Stop {
+        let mut budget = self.step_limit.unwrap_or(u64::MAX);
+        loop {
+            if budget == 0 {
+                return Stop::StepLimit;
+            }
+            budget -= 1;
+            match self.step_once() {
+                Ok(true) => (),
+                Ok(false) => return Stop::Halted,
+                Err(err) => return err.into(),
+            }
+            if let Some(stop) = self.check_watchpoints() {
+                return stop;
+            }
+            if let Some(span) = self.location().span() {
+                if let Some(&offset) = self.breakpoints.range(span.start..span.end).next() {
+                    return Stop::Breakpoint(offset);
+                }
+            }
+            if !keep_going(&self.frames) {
+                return Stop::Done;
+            }
+        }
+    }
+
+    /// Returns false once the program has finished.
+    fn step_once(&mut self) -> Result<bool, ExecError> {
+        let depth = self.frames.len() - 1;
+        let index = self.frames[depth];
+        let body = navigate(&self.program.0, &self.frames[..depth]);
+
+        let Some(instruction) = body.get(index) else {
+            if depth == 0 {
+                return Ok(false);
+            }
+            self.frames.pop();
+            self.steps += 1;
+            if current(&mut self.exec)? != 0 {
+                self.frames.push(0);
+            } else {
+                self.frames[depth - 1] += 1;
+            }
+            return Ok(true);
+        };
+
+        self.steps += 1;
+        match instruction {
+            Instruction::Loop(..) if current(&mut self.exec)? != 0 => self.frames.push(0),
+            Instruction::Loop(..) => self.frames[depth] += 1,
+            i => {
+                if !matches!(i, Instruction::Move(_)) {
+                    current(&mut self.exec)?;
+                }
+                self.exec.step(i)?;
+                self.frames[depth] += 1;
+            }
+        }
+        Ok(true)
+    }
+
+    fn check_watchpoints(&mut self) -> Option<Stop> {
+        let tape = self.exec.cells();
+        let mut stop = None;
+        for (&cell, old) in &mut self.watchpoints {
+            let new = tape.get(cell).copied().unwrap_or(0);
+            if new != *old {
+                stop = stop.or(Some(Stop::Watchpoint {
+                    cell,
+                    old: *old,
+                    new,
+                }));
+                *old = new;
+            }
+        }
+        stop
+    }
+
+    fn spans(&self, depth: usize) -> &[SpanNode] {
+        let mut spans = &self.spans[..];
+        for &index in &self.frames[..depth] {
+            spans = spans[index].body.as_deref().unwrap();
+        }
+        spans
+    }
+
+    /// Every span the debugger can stop at.
+    fn instruction_heads(&self) -> impl Iterator<Item = Span> {
+        fn walk(nodes: &[SpanNode], heads: &mut Vec<Span>) {
+            for node in nodes {
+                heads.push(head(node));
+                if let Some(body) = &node.body {
+                    walk(body, heads);
+                    heads.push(Span::at(node.span.end - 1));
+                }
+            }
+        }
+        let mut heads = Vec::new();
+        walk(&self.spans, &mut heads);
+        heads.into_iter()
+    }
+}
+
+/// The cell under the pointer, growing the tape to reach it. Everything the
+/// debugger builds only ever touches that cell.
+fn current(exec: &mut TreeExec<Vec<u8>, Cursor<Vec<u8>>>) -> Result<u8, ExecError> {
+    exec.step(&Instruction::BoundsCheck(BoundsRange {
+        start: 0,
+        length: 1,
+    }))?;
+    Ok(exec.cells()[exec.pointer() as usize])
+}
+
+fn navigate<'a>(mut body: &'a [Instruction], path: &[usize]) -> &'a [Instruction] {
+    for &index in path {
+        let Instruction::Loop(_, _, inner) = &body[index] else {
+            unreachable!()
+        };
+        body = inner;
+    }
+    body
+}
+
+/// The part of an instruction's span a breakpoint or location refers to: the
+/// `[` of a loop, or the whole run of characters otherwise.
+fn head(node: &SpanNode) -> Span {
+    if node.body.is_some() {
+        Span::at(node.span.start)
+    } else {
+        node.span
+    }
+}
+
+type Built = (Vec<Instruction>, Vec<SpanNode>, Option<Span>);
+
+fn build<I: Iterator<Item = (Token, Span)>>(tokens: &mut Peekable<I>) -> Result<Built, String> {
+    let mut body = Vec::new();
+    let mut spans = Vec::new();
+
+    while let Some((token, span)) = tokens.next() {
+        let (instruction, span, inner) = match token {
+            Token::Plus | Token::Minus => {
+                let mut amount = 0i8;
+                let mut span = span;
+                let mut token = token;
+                loop {
+                    amount = amount.wrapping_add(if token == Token::Plus { 1 } else { -1 });
+                    match tokens.peek() {
+                        Some(&(next @ (Token::Plus | Token::Minus), next_span)) => {
+                            token = next;
+                            span = span.to(next_span);
+                            tokens.next();
+                        }
+                        _ => break,
+                    }
+                }
+                (Instruction::Modify(0, amount), span, None)
+            }
+            Token::Next | Token::Previous => {
+                let mut amount = 0isize;
+                let mut span = span;
+                let mut token = token;
+                loop {
+                    amount += if token == Token::Next { 1 } else { -1 };
+                    match tokens.peek() {
+                        Some(&(next @ (Token::Next | Token::Previous), next_span)) => {
+                            token = next;
+                            span = span.to(next_span);
+                            tokens.next();
+                        }
+                        _ => break,
+                    }
+                }
+                (Instruction::Move(amount), span, None)
+            }
+            Token::Dot => (Instruction::Output(0), span, None),
+            Token::Comma => (Instruction::Input(0), span, None),
+            Token::Open => {
+                let (inner, inner_spans, close) = build(tokens)?;
+                let Some(close) = close else {
+                    return Err(format!("unmatched [ at offset {}", span.start));
+                };
+                let span = span.to(close);
+                (Instruction::Loop(false, 0, inner), span, Some(inner_spans))
+            }
+            Token::Close => return Ok((body, spans, Some(span))),
+            Token::Dump | Token::EndOfCode | Token::Data(_) => continue,
+        };
+        body.push(instruction);
+        spans.push(SpanNode { span, body: inner });
+    }
+
+    Ok((body, spans, None))
+}
@@ -0,0 +1,238 @@
+use super::{Debugger, Location, Stop};
+use crate::frontend::lexer::Span;
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "(rfdb) ";
+const TAPE_RADIUS: usize = 4;
+
+const HELP: &str = "\
+step, s              execute one instruction, entering loops
+next, n              execute one instruction, running loops to completion
+finish, f            run until the current loop is left
+continue, c [steps]  run until a breakpoint, a watchpoint, the end or the
+                     step limit, or for at most `steps` steps
+break, b <pos>       stop at a source position, as line:col or a byte offset
+delete, d <pos>      remove a breakpoint
+watch, w <cell>      stop when a cell changes
+unwatch <cell>       remove a watchpoint
+tape, t [radius]     show the cells around the pointer
+output, o            show output written since it was last shown
+where, l             show the current source position
+info, i              list breakpoints and watchpoints
+help, h              show this message
+quit, q              leave the debugger
+
+An empty line repeats the last command.";
+
+/// Reads commands line by line until `quit` or the end of `commands`.
+pub fn run(
+    debugger: &mut Debugger,
+    src: &str,
+    commands: impl BufRead,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut repl = Repl {
+        debugger,
+        src,
+        out: &mut out,
+    };
+    repl.show_location()?;
+    repl.prompt()?;
+
+    let mut last = String::new();
+    for line in commands.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = line;
+        }
+        if !repl.command(&last)? {
+            return Ok(());
+        }
+        repl.prompt()?;
+    }
+    writeln!(out)
+}
+
+struct Repl<'a, W> {
+    debugger: &'a mut Debugger,
+    src: &'a str,
+    out: &'a mut W,
+}
+impl<W: Write> Repl<'_, W> {
+    /// Returns false when the user asked to quit.
+    fn command(&mut self, line: &str) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let arg = words.next();
+
+        match command {
+            "" => (),
+            "s" | "step" => self.stopped(|d| d.step())?,
+            "n" | "next" => self.stopped(|d| d.step_over())?,
+            "f" | "finish" => self.stopped(|d| d.finish())?,
+            "c" | "continue" => match arg.map(str::parse) {
+                Some(Ok(steps)) => self.stopped(|d| d.resume_for(steps))?,
+                Some(Err(_)) => writeln!(self.out, "continue expects a number of steps")?,
+                None => self.stopped(|d| d.resume())?,
+            },
+            "b" | "break" => match arg.and_then(|a| self.offset(a)) {
+                Some(offset) if self.debugger.add_breakpoint(offset) => {
+                    writeln!(self.out, "breakpoint at {}", self.position(offset))?
+                }
+                Some(_) => writeln!(self.out, "no instruction there")?,
+                None => writeln!(self.out, "break expects line:col or an offset")?,
+            },
+            "d" | "delete" => match arg.and_then(|a| self.offset(a)) {
+                Some(offset) if self.debugger.remove_breakpoint(offset) => {
+                    writeln!(self.out, "deleted breakpoint at {}", self.position(offset))?
+                }
+                _ => writeln!(self.out, "no such breakpoint")?,
+            },
+            "w" | "watch" => match arg.and_then(|a| a.parse().ok()) {
+                Some(cell) => {
+                    self.debugger.watch(cell);
+                    writeln!(self.out, "watching cell {cell}")?
+                }
+                None => writeln!(self.out, "watch expects a cell index")?,
+            },
+            "unwatch" => match arg.and_then(|a| a.parse().ok()) {
+                Some(cell) if self.debugger.unwatch(cell) => {
+                    writeln!(self.out, "stopped watching cell {cell}")?
+                }
+                _ => writeln!(self.out, "no such watchpoint")?,
+            },
+            "t" | "tape" => {
+                let radius = arg.and_then(|a| a.parse().ok()).unwrap_or(TAPE_RADIUS);
+                self.show_tape(radius)?
+            }
+            "o" | "output" => self.show_output()?,
+            "l" | "where" => self.show_location()?,
+            "i" | "info" => self.show_info()?,
+            "h" | "help" => writeln!(self.out, "{HELP}")?,
+            "q" | "quit" => return Ok(false),
+            other => writeln!(self.out, "unknown command {other}, try help")?,
+        }
+        Ok(true)
+    }
+
+    fn stopped(&mut self, run: impl FnOnce(&mut Debugger) -> Stop) -> io::Result<()> {
+        match run(self.debugger) {
+            Stop::Done => (),
+            Stop::Halted => writeln!(
+                self.out,
+                "program finished after {} steps",
+                self.debugger.steps()
+            )?,
+            Stop::Breakpoint(offset) => {
+                writeln!(self.out, "breakpoint at {}", self.position(offset))?
+            }
+            Stop::Watchpoint { cell, old, new } => {
+                writeln!(self.out, "cell {cell} changed from {old} to {new}")?
+            }
+            Stop::NegativePointer => writeln!(self.out, "pointer moved left of cell 0")?,
+            Stop::StepLimit => writeln!(
+                self.out,
+                "paused after {} steps, continue to run further",
+                self.debugger.steps()
+            )?,
+            Stop::Failed(err) => writeln!(self.out, "{err}")?,
+        }
+        self.show_location()
+    }
+
+    fn show_location(&mut self) -> io::Result<()> {
+        let Some(span) = self.debugger.location().span() else {
+            return writeln!(self.out, "halted");
+        };
+        let (line, col) = span.line_col(self.src);
+        let text = self.src.lines().nth(line - 1).unwrap_or("");
+        let width = (span.end - span.start).min(text.len() + 1 - col).max(1);
+        let label = format!("{line}:{col}");
+        let what = match self.debugger.location() {
+            Location::LoopEnd(_) => "  (end of loop)",
+            _ => "",
+        };
+        writeln!(self.out, "{label} | {text}{what}")?;
+        writeln!(
+            self.out,
+            "{} | {}{}",
+            " ".repeat(label.len()),
+            " ".repeat(col - 1),
+            "^".repeat(width)
+        )
+    }
+
+    fn show_tape(&mut self, radius: usize) -> io::Result<()> {
+        let (start, cells) = self.debugger.tape_around_pointer(radius);
+        let pointer = self.debugger.pointer();
+        let cells: Vec<_> = (start..)
+            .zip(cells)
+            .map(|(i, c)| {
+                if i as isize == pointer {
+                    format!("[{i}: {c}]")
+                } else {
+                    format!("{i}: {c}")
+                }
+            })
+            .collect();
+        writeln!(self.out, "{}", cells.join("  "))
+    }
+
+    fn show_output(&mut self) -> io::Result<()> {
+        let pending = self.debugger.take_pending_output();
+        if pending.is_empty() {
+            return writeln!(self.out, "no pending output");
+        }
+        let escaped: String = pending
+            .iter()
+            .flat_map(|&b| std::ascii::escape_default(b))
+            .map(char::from)
+            .collect();
+        writeln!(self.out, "\"{escaped}\"")
+    }
+
+    fn show_info(&mut self) -> io::Result<()> {
+        let breakpoints: Vec<_> = self
+            .debugger
+            .breakpoints()
+            .map(|offset| self.position(offset))
+            .collect();
+        let watchpoints: Vec<_> = self
+            .debugger
+            .watchpoints()
+            .map(|cell| cell.to_string())
+            .collect();
+        writeln!(self.out, "breakpoints: {}", breakpoints.join(", "))?;
+        writeln!(self.out, "watching: {}", watchpoints.join(", "))?;
+        writeln!(
+            self.out,
+            "{} steps, pointer at {}",
+            self.debugger.steps(),
+            self.debugger.pointer()
+        )
+    }
+
+    fn position(&self, offset: usize) -> String {
+        let (line, col) = Span::at(offset).line_col(self.src);
+        format!("{line}:{col}")
+    }
+    /// Parses `line:col` (1-based) or a plain byte offset.
+    fn offset(&self, arg: &str) -> Option<usize> {
+        let Some((line, col)) = arg.split_once(':') else {
+            return arg.parse().ok();
+        };
+        let (line, col): (usize, usize) = (line.parse().ok()?, col.parse().ok()?);
+        let start: usize = self
+            .src
+            .split_inclusive('\n')
+            .take(line.checked_sub(1)?)
+            .map(str::len)
+            .sum();
+        Some(start + col.checked_sub(1)?)
+    }
+
+    fn prompt(&mut self) -> io::Result<()> {
+        write!(self.out, "{PROMPT}")?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,461 @@
+use super::expr_tree::{CellOffset, Instruction, Program};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+/// Turns a program back into Brainfuck.
+///
+/// Cells keep their positions whenever every temporary the program needs can
+/// be placed on a cell known to be zero. Otherwise the tape is spread out so
+/// that every cell is followed by a scratch cell, which changes the layout seen
+/// by `#` dumps but not the output.
+///
+/// An unbalanced `If` can only be emitted as a loop whose body is known to
+/// clear the cell the loop ends on, and fails otherwise.
+pub fn emit(program: &Program) -> Result<String, EmitError> {
+    match Emitter::new(1).emit(&program.0) {
+        Err(Failed::NoScratch) => Emitter::new(2).emit(&program.0),
+        emitted => emitted,
+    }
+    .map_err(|failed| match failed {
+        Failed::Emit(err) => err,
+        Failed::NoScratch => unreachable!("the spread layout always has scratch cells"),
+    })
+}
+
+/// A construct that has no Brainfuck equivalent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmitError {
+    /// An `If` that moves the pointer without clearing the cell it ends on.
+    UnbalancedIf,
+}
+impl Display for EmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnbalancedIf => write!(f, "cannot emit an if that moves the pointer"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Failed {
+    NoScratch,
+    Emit(EmitError),
+}
+
+/// Positions are physical cells relative to where the pointer started, or
+/// to an arbitrary point after a loop whose trip count is unknown.
+#[derive(Clone)]
+struct Emitter {
+    out: String,
+    spread: isize,
+    frame: isize,
+    head: isize,
+    known: HashMap<isize, u8>,
+    /// Whether the frame is still absolute and every untouched cell is zero.
+    fresh: bool,
+    touched: HashSet<isize>,
+}
+impl Emitter {
+    fn new(spread: isize) -> Self {
+        Self {
+            out: String::new(),
+            spread,
+            frame: 0,
+            head: 0,
+            known: HashMap::new(),
+            fresh: true,
+            touched: HashSet::new(),
+        }
+    }
+
+    fn emit(mut self, instructions: &[Instruction]) -> Result<String, Failed> {
+        self.emit_block(instructions)?;
+        Ok(self.out)
+    }
+
+    fn emit_block(&mut self, instructions: &[Instruction]) -> Result<(), Failed> {
+        let mut consumed = vec![false; instructions.len()];
+        for (i, instruction) in instructions.iter().enumerate() {
+            if consumed[i] {
+                continue;
+            }
+
+            use Instruction::*;
+            match *instruction {
+                Modify(cell, amount) => {
+                    let cell = self.cell(cell);
+                    self.goto(cell);
+                    self.adjust(0, amount as u8);
+                    match self.value(cell) {
+                        Some(value) => self.learn(cell, value.wrapping_add_signed(amount)),
+                        None => self.wrote(cell),
+                    }
+                }
+                Move(amount) => self.frame += amount * self.spread,
+                Output(cell) => {
+                    self.goto(self.cell(cell));
+                    self.out.push('.');
+                }
+                Input(cell) => {
+                    let cell = self.cell(cell);
+                    self.goto(cell);
+                    self.out.push(',');
+                    self.wrote(cell);
+                }
+                Set(cell, value) => self.set(self.cell(cell), value),
+                Print(ref bytes) => self.print(bytes)?,
+                Dump(cell) => {
+                    self.goto(self.cell(cell));
+                    self.out.push('#');
+                }
+                AddMultiple {
+                    target,
+                    base,
+                    factor,
+                } => match find_group(instructions, i, &consumed) {
+                    Some(group) => {
+                        group.iter().for_each(|&j| consumed[j] = true);
+                        self.clearing_multiply(base, &group, instructions);
+                    }
+                    None => self.multiply(target, base, factor, false)?,
+                },
+                Copy {
+                    target,
+                    base,
+                    factor,
+                } => match find_group(instructions, i, &consumed) {
+                    Some(group) => {
+                        group.iter().for_each(|&j| consumed[j] = true);
+                        self.clearing_multiply(base, &group, instructions);
+                    }
+                    None => self.multiply(target, base, factor, true)?,
+                },
+                BoundsCheck(_) => (),
+                Loop(_, cell, ref body) => {
+                    let condition = self.cell(cell);
+                    if self.is_zero(condition) {
+                        continue;
+                    }
+                    self.goto(condition);
+                    self.out.push('[');
+                    self.forget_all();
+                    self.emit_block(body)?;
+                    self.close(cell);
+                }
+                If(balanced, cell, ref body) => self.emit_if(balanced, cell, body)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_if(
+        &mut self,
+        balanced: bool,
+        cell: CellOffset,
+        body: &[Instruction],
+    ) -> Result<(), Failed> {
+        let condition = self.cell(cell);
+        if self.is_zero(condition) {
+            return Ok(());
+        }
+
+        let before = self.clone();
+        self.goto(condition);
+        self.out.push('[');
+        self.forget_all();
+        self.emit_block(body)?;
+        if self.is_zero(self.cell(cell)) {
+            self.close(cell);
+            return Ok(());
+        }
+        if !balanced {
+            return Err(Failed::Emit(EmitError::UnbalancedIf));
+        }
+        *self = before;
+
+        // Runs the body under a flag that is cleared on entry, with the
+        // condition copied into the flag and restored through a temporary.
+        if self.spread == 1 {
+            return Err(Failed::NoScratch);
+        }
+        let temp = self.temp(condition, &[])?;
+        let flag = self.temp(condition, &[temp])?;
+        self.goto(condition);
+        self.out.push('[');
+        for target in [temp, flag] {
+            self.goto(target);
+            self.out.push('+');
+        }
+        self.goto(condition);
+        self.out.push_str("-]");
+        self.goto(temp);
+        self.out.push('[');
+        self.goto(condition);
+        self.out.push('+');
+        self.goto(temp);
+        self.out.push_str("-]");
+
+        self.goto(flag);
+        self.out.push_str("[[-]");
+        self.forget_all();
+        self.emit_block(body)?;
+        self.goto(flag);
+        self.out.push(']');
+        self.forget_all();
+        Ok(())
+    }
+
+    /// Ends a loop on `cell`, which is zero once it exits.
+    fn close(&mut self, cell: CellOffset) {
+        let condition = self.cell(cell);
+        self.goto(condition);
+        self.out.push(']');
+        self.forget_all();
+        self.learn(condition, 0);
+    }
+
+    /// Emits the group found by [`find_group`]: the multiplications of `base`
+    /// followed by clearing it, as a single loop counting `base` down.
+    fn clearing_multiply(
+        &mut self,
+        base: CellOffset,
+        group: &[usize],
+        instructions: &[Instruction],
+    ) {
+        let base = self.cell(base);
+        let mut targets = Vec::new();
+        for &i in group {
+            match instructions[i] {
+                Instruction::AddMultiple { target, factor, .. } => {
+                    targets.push((self.cell(target), factor as u8))
+                }
+                Instruction::Copy { target, factor, .. } => {
+                    let target = self.cell(target);
+                    self.set(target, 0);
+                    targets.push((target, factor as u8));
+                }
+                _ => (),
+            }
+        }
+        if self.is_zero(base) {
+            return;
+        }
+
+        self.transfer(base, &targets);
+        targets.iter().for_each(|&(target, _)| self.wrote(target));
+        self.learn(base, 0);
+    }
+
+    /// Adds or copies a multiple of `base` while keeping it, by moving it
+    /// through a temporary and back.
+    fn multiply(
+        &mut self,
+        target: CellOffset,
+        base: CellOffset,
+        factor: i8,
+        copy: bool,
+    ) -> Result<(), Failed> {
+        let (target, base) = (self.cell(target), self.cell(base));
+        if self.is_zero(base) {
+            if copy {
+                self.set(target, 0);
+            }
+            return Ok(());
+        }
+        let temp = self.temp(base, &[target, base])?;
+
+        if target == base {
+            let factor = if copy { factor } else { factor.wrapping_add(1) };
+            self.transfer(base, &[(temp, 1)]);
+            self.transfer(temp, &[(base, factor as u8)]);
+        } else {
+            if copy {
+                self.set(target, 0);
+            }
+            self.transfer(base, &[(target, factor as u8), (temp, 1)]);
+            self.transfer(temp, &[(base, 1)]);
+        }
+        self.wrote(target);
+        Ok(())
+    }
+    fn transfer(&mut self, from: isize, to: &[(isize, u8)]) {
+        self.goto(from);
+        self.out.push_str("[-");
+        for &(target, amount) in to {
+            self.goto(target);
+            self.adjust(0, amount);
+        }
+        self.goto(from);
+        self.out.push(']');
+    }
+
+    fn print(&mut self, bytes: &[u8]) -> Result<(), Failed> {
+        let temp = self.temp(self.head, &[])?;
+        self.goto(temp);
+        let mut value = 0;
+        for &byte in bytes {
+            self.adjust(value, byte);
+            self.out.push('.');
+            value = byte;
+        }
+        self.clear_from(value);
+        Ok(())
+    }
+
+    fn set(&mut self, cell: isize, value: u8) {
+        if self.value(cell) == Some(value) {
+            return;
+        }
+        self.goto(cell);
+        match self.value(cell) {
+            Some(known) if distance(known, value) <= 3 + distance(0, value) => {
+                self.adjust(known, value)
+            }
+            _ => {
+                self.out.push_str("[-]");
+                self.adjust(0, value);
+            }
+        }
+        self.learn(cell, value);
+    }
+    fn clear_from(&mut self, value: u8) {
+        if distance(value, 0) > 3 {
+            self.out.push_str("[-]");
+        } else {
+            self.adjust(value, 0);
+        }
+    }
+    fn adjust(&mut self, from: u8, to: u8) {
+        let c = if to.wrapping_sub(from) <= 128 {
+            '+'
+        } else {
+            '-'
+        };
+        self.out
+            .extend(std::iter::repeat_n(c, distance(from, to) as usize));
+    }
+
+    fn cell(&self, cell: CellOffset) -> isize {
+        self.frame + cell * self.spread
+    }
+    fn goto(&mut self, cell: isize) {
+        let distance = cell - self.head;
+        let c = if distance < 0 { '<' } else { '>' };
+        self.out
+            .extend(std::iter::repeat_n(c, distance.unsigned_abs()));
+        self.head = cell;
+    }
+
+    fn value(&self, cell: isize) -> Option<u8> {
+        match self.known.get(&cell) {
+            Some(&value) => Some(value),
+            None => (self.fresh && cell >= 0 && !self.touched.contains(&cell)).then_some(0),
+        }
+    }
+    fn is_zero(&self, cell: isize) -> bool {
+        self.value(cell) == Some(0)
+    }
+    fn learn(&mut self, cell: isize, value: u8) {
+        self.known.insert(cell, value);
+        if self.fresh {
+            self.touched.insert(cell);
+        }
+    }
+    fn wrote(&mut self, cell: isize) {
+        self.known.remove(&cell);
+        if self.fresh {
+            self.touched.insert(cell);
+        }
+    }
+    fn forget_all(&mut self) {
+        self.known.clear();
+        self.fresh = false;
+        self.touched.clear();
+    }
+
+    /// The zero cell closest to `near` outside of `avoid`, which must be zero
+    /// again by the time it is used for anything else.
+    fn temp(&self, near: isize, avoid: &[isize]) -> Result<isize, Failed> {
+        let usable = |cell: &isize| !avoid.contains(cell);
+        if self.spread > 1 {
+            let first = near - near.rem_euclid(self.spread) + 1;
+            return Ok((0..).map(|i| first + i * self.spread).find(usable).unwrap());
+        }
+
+        let mut candidates: Vec<isize> = self
+            .known
+            .iter()
+            .filter(|&(cell, &value)| value == 0 && usable(cell))
+            .map(|(&cell, _)| cell)
+            .collect();
+        if self.fresh {
+            let reach = (self.touched.len() + avoid.len() + 1) as isize;
+            candidates.extend(
+                (near - reach..=near + reach).filter(|&cell| self.is_zero(cell) && usable(&cell)),
+            );
+        }
+        candidates
+            .into_iter()
+            .min_by_key(|&cell| ((cell - near).abs(), cell))
+            .ok_or(Failed::NoScratch)
+    }
+}
+
+/// The number of `+` or `-` needed to go from one value to the other.
+fn distance(from: u8, to: u8) -> u8 {
+    to.wrapping_sub(from).min(from.wrapping_sub(to))
+}
+
+/// Finds the multiplications of the base of `instructions[start]` that are
+/// followed by clearing the base, returning them and the clear. The cells
+/// involved must not be touched by anything skipped over in between.
+fn find_group(instructions: &[Instruction], start: usize, consumed: &[bool]) -> Option<Vec<usize>> {
+    use Instruction::*;
+    let multiple = |i: &Instruction| match *i {
+        AddMultiple { target, base, .. } | Copy { target, base, .. } => Some((target, base)),
+        _ => None,
+    };
+    let (first, base) = multiple(&instructions[start])?;
+    if first == base {
+        return None;
+    }
+
+    let mut group = vec![start];
+    let mut cells = vec![base, first];
+    let mut skipped: Vec<&Instruction> = Vec::new();
+    for (j, i) in instructions.iter().enumerate().skip(start + 1) {
+        if consumed[j] {
+            continue;
+        }
+        match *i {
+            BoundsCheck(_) => (),
+            Set(cell, 0) if cell == base => {
+                group.push(j);
+                let clear = skipped.iter().all(|&i| !touches(i, &cells));
+                return clear.then_some(group);
+            }
+            _ => match multiple(i) {
+                Some((target, b)) if b == base && !cells.contains(&target) => {
+                    group.push(j);
+                    cells.push(target);
+                }
+                _ if matches!(i, Move(_) | Dump(_) | Loop(..) | If(..)) => return None,
+                _ => skipped.push(i),
+            },
+        }
+    }
+    None
+}
+fn touches(i: &Instruction, cells: &[CellOffset]) -> bool {
+    use Instruction::*;
+    match *i {
+        Modify(cell, _) | Output(cell) | Input(cell) | Set(cell, _) => cells.contains(&cell),
+        AddMultiple { target, base, .. } | Copy { target, base, .. } => {
+            cells.contains(&target) || cells.contains(&base)
+        }
+        Print(_) | BoundsCheck(_) => false,
+        Move(_) | Dump(_) | Loop(..) | If(..) => true,
+    }
+}
@@ -0,0 +1,124 @@
+use super::expr_tree::{BoundsRange, CellOffset, Instruction, Program};
+use crate::ir::{
+    exec::{dump_tape, Io, Tape},
+    limits::{ExecError, Limits, Meter},
+};
+use std::io::{stderr, Read, Write};
+
+pub struct TreeExec<O, I> {
+    tape: Tape,
+    pointer: isize,
+    io: Io<O, I>,
+    meter: Meter,
+}
+impl<O: Write, I: Read> TreeExec<O, I> {
+    pub fn new(stdout: O, stdin: I) -> Self {
+        Self {
+            tape: Tape::new(),
+            pointer: 0,
+            io: Io::new(stdout, stdin),
+            meter: Meter::default(),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.tape.set_max_len(limits.max_tape);
+        self.meter = Meter::new(limits);
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        self.tape.cells()
+    }
+    pub fn pointer(&self) -> isize {
+        self.pointer
+    }
+    pub fn stdout(&self) -> &O {
+        self.io.stdout()
+    }
+
+    pub fn exec_program(&mut self, program: &Program) -> Result<(), ExecError> {
+        self.meter.reset();
+        self.pointer = 0;
+        self.exec_all(&program.0)
+    }
+    /// Executes a single instruction where the last one left off, running the
+    /// whole body of a loop or if. Lets a caller such as the debugger drive
+    /// the program itself.
+    pub fn step(&mut self, i: &Instruction) -> Result<(), ExecError> {
+        self.exec(i)
+    }
+
+    fn exec_all(&mut self, body: &[Instruction]) -> Result<(), ExecError> {
+        self.meter.tick(body.len() as u64, self.tape.len())?;
+        body.iter().try_for_each(|i| self.exec(i))
+    }
+    fn exec(&mut self, i: &Instruction) -> Result<(), ExecError> {
+        use Instruction::*;
+        match *i {
+            Modify(cell, amount) => {
+                let cell = self.cell(cell)?;
+                *cell = cell.wrapping_add_signed(amount);
+            }
+            Move(amount) => self.pointer += amount,
+            Output(cell) => {
+                let val = *self.cell(cell)?;
+                self.io.write(val)?;
+            }
+            Input(cell) => {
+                let old = *self.cell(cell)?;
+                *self.cell(cell)? = self.io.read(old)?;
+            }
+            Set(cell, val) => *self.cell(cell)? = val,
+            Print(ref bytes) => bytes.iter().try_for_each(|&b| self.io.write(b))?,
+            Dump(cell) => dump_tape(stderr().lock(), self.tape.cells(), self.pointer + cell)?,
+            AddMultiple {
+                target,
+                base,
+                factor,
+            } => {
+                let addend = self.cell(base)?.wrapping_mul(factor as u8);
+                let cell = self.cell(target)?;
+                *cell = cell.wrapping_add(addend);
+            }
+            Copy {
+                target,
+                base,
+                factor,
+            } => {
+                let val = self.cell(base)?.wrapping_mul(factor as u8);
+                *self.cell(target)? = val;
+            }
+            BoundsCheck(BoundsRange { start, length }) => {
+                self.index(start)?;
+                let end = self.index(start + length as CellOffset)?;
+                if !self.tape.grow_to(end) {
+                    return Err(self.meter.tape_limit(self.tape.len()));
+                }
+            }
+            Loop(_, cell, ref body) => {
+                while *self.cell(cell)? != 0 {
+                    self.exec_all(body)?;
+                }
+            }
+            If(_, cell, ref body) => {
+                if *self.cell(cell)? != 0 {
+                    self.exec_all(body)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index(&self, offset: CellOffset) -> Result<usize, ExecError> {
+        let index = self.pointer + offset;
+        if index < 0 {
+            return Err(self.meter.left_of_tape(self.tape.len()));
+        }
+        Ok(index as usize)
+    }
+    fn cell(&mut self, offset: CellOffset) -> Result<&mut u8, ExecError> {
+        let index = self.index(offset)?;
+        Ok(&mut self.tape[index])
+    }
+}
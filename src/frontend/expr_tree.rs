@@ -1,21 +1,30 @@
 use std::fmt::Display;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program(pub Vec<Instruction>);
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Modify(CellOffset, i8),
     Move(isize),
     Output(CellOffset),
     Input(CellOffset),
     Set(CellOffset, u8),
+    Print(Vec<u8>),
+    Dump(CellOffset),
 
     AddMultiple {
         target: CellOffset,
         base: CellOffset,
         factor: i8,
     },
+    Copy {
+        target: CellOffset,
+        base: CellOffset,
+        factor: i8,
+    },
 
     BoundsCheck(BoundsRange),
 
@@ -30,12 +39,21 @@ impl Instruction {
             _ => false,
         }
     }
+    /// Whether this reads input or writes output, including inside its body.
+    pub fn performs_io(&self) -> bool {
+        match self {
+            Self::Output(_) | Self::Input(_) | Self::Print(_) | Self::Dump(_) => true,
+            Self::If(_, _, body) | Self::Loop(_, _, body) => body.iter().any(Self::performs_io),
+            _ => false,
+        }
+    }
 }
 
 pub type CellOffset = isize;
 pub type BlockBalanced = bool;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoundsRange {
     pub start: isize,
     pub length: usize,
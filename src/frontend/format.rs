@@ -0,0 +1,230 @@
+use super::lexer::{lex_spanned_with, Dialect, Span, Token};
+use std::io::Cursor;
+
+/// How [`format`] lays out a program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// The longest line, indentation included, unless a single word of a
+    /// comment is longer.
+    pub width: usize,
+    /// Spaces per loop nesting level.
+    pub indent: usize,
+    /// Keep comments, each on its own lines before the code following it.
+    pub comments: bool,
+}
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 2,
+            comments: true,
+        }
+    }
+}
+
+/// Lays out a program with every loop containing other loops or comments on
+/// lines of its own, its body indented one level deeper, and runs of commands
+/// wrapped at the configured width.
+pub fn format(src: &str, dialect: Dialect, options: FormatOptions) -> Result<String, String> {
+    let mut tokens = lex_spanned_with(Cursor::new(src), dialect);
+    let mut last = 0;
+    let items = parse(src, &mut tokens, &mut last, None)?;
+
+    let mut formatter = Formatter {
+        options,
+        out: String::new(),
+        line: String::new(),
+    };
+    formatter.block(&items, 0);
+    formatter.flush(0);
+    Ok(formatter.out)
+}
+
+/// Strips everything but commands and cancels adjacent `+-` and `<>` pairs.
+pub fn minify(src: &str, dialect: Dialect) -> String {
+    let mut out = String::new();
+    for (token, span) in lex_spanned_with(Cursor::new(src), dialect) {
+        if token == Token::EndOfCode {
+            out.push_str(&src[span.start..]);
+            break;
+        }
+        let c = command(token);
+        let inverse = match c {
+            '+' => Some('-'),
+            '-' => Some('+'),
+            '>' => Some('<'),
+            '<' => Some('>'),
+            _ => None,
+        };
+        if inverse.is_some_and(|inverse| out.ends_with(inverse)) {
+            out.pop();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+enum Item<'a> {
+    Command(char),
+    Comment(String),
+    Loop(Vec<Item<'a>>),
+    /// The `!` ending the code and the input following it, kept verbatim.
+    Data(&'a str),
+}
+
+fn parse<'a>(
+    src: &'a str,
+    tokens: &mut impl Iterator<Item = (Token, Span)>,
+    last: &mut usize,
+    open: Option<Span>,
+) -> Result<Vec<Item<'a>>, String> {
+    let mut items = Vec::new();
+    loop {
+        let Some((token, span)) = tokens.next() else {
+            comment(&src[*last..], &mut items);
+            return match open {
+                Some(open) => Err(unmatched('[', open, src)),
+                None => Ok(items),
+            };
+        };
+        comment(&src[*last..span.start], &mut items);
+        *last = span.end;
+
+        match token {
+            Token::Open => items.push(Item::Loop(parse(src, tokens, last, Some(span))?)),
+            Token::Close if open.is_none() => return Err(unmatched(']', span, src)),
+            Token::Close => return Ok(items),
+            Token::EndOfCode => {
+                items.push(Item::Data(&src[span.start..]));
+                *last = src.len();
+                tokens.for_each(drop);
+            }
+            token => items.push(Item::Command(command(token))),
+        }
+    }
+}
+fn comment(text: &str, items: &mut Vec<Item>) {
+    let words: Vec<_> = text.split_whitespace().collect();
+    if !words.is_empty() {
+        items.push(Item::Comment(words.join(" ")));
+    }
+}
+fn unmatched(c: char, span: Span, src: &str) -> String {
+    let (line, col) = span.line_col(src);
+    format!("unmatched {c} at {line}:{col}")
+}
+
+fn command(token: Token) -> char {
+    match token {
+        Token::Plus => '+',
+        Token::Minus => '-',
+        Token::Next => '>',
+        Token::Previous => '<',
+        Token::Dot => '.',
+        Token::Comma => ',',
+        Token::Open => '[',
+        Token::Close => ']',
+        Token::Dump => '#',
+        Token::EndOfCode => '!',
+        Token::Data(_) => unreachable!("data is kept verbatim"),
+    }
+}
+
+struct Formatter {
+    options: FormatOptions,
+    out: String,
+    /// The run of commands not written out yet.
+    line: String,
+}
+impl Formatter {
+    fn block(&mut self, items: &[Item], depth: usize) {
+        for item in items {
+            match item {
+                Item::Command(c) => {
+                    if self.line.len() >= self.available(depth) {
+                        self.flush(depth);
+                    }
+                    self.line.push(*c);
+                }
+                Item::Comment(text) if self.options.comments => {
+                    self.flush(depth);
+                    self.comment(text, depth);
+                }
+                Item::Comment(_) => (),
+                Item::Loop(body) => match self.inline(body) {
+                    Some(text) if text.len() <= self.available(depth) => {
+                        if self.line.len() + text.len() > self.available(depth) {
+                            self.flush(depth);
+                        }
+                        self.line.push_str(&text);
+                    }
+                    _ => {
+                        self.flush(depth);
+                        self.write_line("[", depth);
+                        self.block(body, depth + 1);
+                        self.flush(depth + 1);
+                        self.write_line("]", depth);
+                    }
+                },
+                Item::Data(data) => {
+                    self.flush(depth);
+                    self.out.push_str(data);
+                }
+            }
+        }
+    }
+
+    /// The loop as a single run, if it holds nothing but commands.
+    fn inline(&self, body: &[Item]) -> Option<String> {
+        let mut text = String::from("[");
+        for item in body {
+            match item {
+                Item::Command(c) => text.push(*c),
+                Item::Comment(_) if !self.options.comments => (),
+                _ => return None,
+            }
+        }
+        text.push(']');
+        Some(text)
+    }
+
+    fn comment(&mut self, text: &str, depth: usize) {
+        let mut line = String::new();
+        let mut columns = 0;
+        for word in text.split(' ') {
+            let width = word.chars().count();
+            if columns > 0 && columns + 1 + width > self.available(depth) {
+                self.write_line(&line, depth);
+                line.clear();
+                columns = 0;
+            }
+            if columns > 0 {
+                line.push(' ');
+                columns += 1;
+            }
+            line.push_str(word);
+            columns += width;
+        }
+        self.write_line(&line, depth);
+    }
+
+    fn available(&self, depth: usize) -> usize {
+        self.options
+            .width
+            .saturating_sub(depth * self.options.indent)
+            .max(1)
+    }
+    fn flush(&mut self, depth: usize) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.write_line(&line, depth);
+        }
+    }
+    fn write_line(&mut self, text: &str, depth: usize) {
+        self.out
+            .extend(std::iter::repeat_n(' ', depth * self.options.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
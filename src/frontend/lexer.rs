@@ -1,24 +1,90 @@
 use std::io::{BufRead, BufReader, Read};
 
 pub fn lex<R: BufRead>(src: R) -> impl Iterator<Item = Token> {
+    lex_with(src, Dialect::default())
+}
+pub fn lex_with<R: BufRead>(src: R, dialect: Dialect) -> impl Iterator<Item = Token> {
+    lex_spanned_with(src, dialect).map(|(token, _)| token)
+}
+
+/// Like [`lex`], but also yields where in the source each token came from.
+pub fn lex_spanned<R: BufRead>(src: R) -> impl Iterator<Item = (Token, Span)> {
+    lex_spanned_with(src, Dialect::default())
+}
+pub fn lex_spanned_with<R: BufRead>(
+    src: R,
+    dialect: Dialect,
+) -> impl Iterator<Item = (Token, Span)> {
     let reader = BufReader::new(src);
+    let mut in_data = false;
     reader
         .bytes()
         .map(Result::unwrap)
-        .filter_map(|c| char::from_u32(c as u32))
-        .filter_map(|c| match c {
-            '+' => Some(Token::Plus),
-            '-' => Some(Token::Minus),
-            '>' => Some(Token::Next),
-            '<' => Some(Token::Previous),
-            '.' => Some(Token::Dot),
-            ',' => Some(Token::Comma),
-            '[' => Some(Token::Open),
-            ']' => Some(Token::Close),
-            _ => None,
+        .enumerate()
+        .filter_map(move |(offset, c)| {
+            let token = match c {
+                _ if in_data => Token::Data(c),
+                b'+' => Token::Plus,
+                b'-' => Token::Minus,
+                b'>' => Token::Next,
+                b'<' => Token::Previous,
+                b'.' => Token::Dot,
+                b',' => Token::Comma,
+                b'[' => Token::Open,
+                b']' => Token::Close,
+                b'#' if dialect.debug_dump => Token::Dump,
+                b'!' if dialect.input_separator => {
+                    in_data = true;
+                    Token::EndOfCode
+                }
+                _ => return None,
+            };
+            Some((token, Span::at(offset)))
         })
 }
 
+/// Opt-in extensions to the eight standard commands.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dialect {
+    /// `#` dumps the cells around the pointer to stderr.
+    pub debug_dump: bool,
+    /// The first `!` ends the program; every byte after it is its input.
+    pub input_separator: bool,
+}
+
+/// A range of byte offsets into the source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    pub fn at(offset: usize) -> Self {
+        Self {
+            start: offset,
+            end: offset + 1,
+        }
+    }
+
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+    pub fn contains(self, offset: usize) -> bool {
+        (self.start..self.end).contains(&offset)
+    }
+
+    /// The 1-based line and column of the start of the span.
+    pub fn line_col(self, src: &str) -> (usize, usize) {
+        let before = &src.as_bytes()[..self.start.min(src.len())];
+        let line = before.iter().filter(|&&c| c == b'\n').count() + 1;
+        let col = before.iter().rev().take_while(|&&c| c != b'\n').count() + 1;
+        (line, col)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Token {
     Plus,
@@ -29,4 +95,9 @@ pub enum Token {
     Comma,
     Open,
     Close,
+
+    Dump,
+    EndOfCode,
+    /// A byte of input embedded after [`Token::EndOfCode`].
+    Data(u8),
 }
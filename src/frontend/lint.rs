@@ -0,0 +1,334 @@
+use super::{
+    ast::AstNode,
+    lexer::{lex_spanned_with, Dialect, Span},
+    parser::{parse_spanned, NodeSpan},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::Cursor,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// A loop whose cell is provably zero when it is reached.
+    DeadLoop,
+    /// A loop that neither moves the pointer nor changes its cell.
+    InfiniteLoop,
+    /// The pointer moves left of cell 0 on a path without loops.
+    NegativePointer,
+    /// A run of `+`/`-` or `<`/`>` with both directions in it.
+    CancellingCommands,
+    /// Code after a loop that is entered and never ends.
+    UnreachableCode,
+}
+impl LintKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DeadLoop => "dead-loop",
+            Self::InfiniteLoop => "infinite-loop",
+            Self::NegativePointer => "negative-pointer",
+            Self::CancellingCommands => "cancelling-commands",
+            Self::UnreachableCode => "unreachable-code",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub span: Span,
+    pub message: String,
+}
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.kind.name())
+    }
+}
+
+/// Checks a program for common mistakes, returning the lints in source order.
+pub fn lint(src: &str, dialect: Dialect) -> Result<Vec<Lint>, String> {
+    let tokens = lex_spanned_with(Cursor::new(src), dialect);
+    let (ast, spans) = parse_spanned(tokens).map_err(|err| {
+        let (line, col) = err.span.line_col(src);
+        format!("{err} at {line}:{col}")
+    })?;
+
+    let nodes = Node::zip(src, &ast.0, &spans);
+    let mut lints = Vec::new();
+    block(&nodes, &mut State::start(), &mut lints);
+    lints.sort_by_key(|l| (l.span.start, l.span.end));
+    Ok(lints)
+}
+
+/// An [`AstNode`] with what the lint needs to know about its source.
+struct Node<'a> {
+    ast: &'a AstNode,
+    span: Span,
+    /// The `+-` or `<>` commands the node was merged from, and their span.
+    commands: &'a [u8],
+    commands_span: Span,
+    body: Vec<Node<'a>>,
+}
+impl<'a> Node<'a> {
+    fn zip(src: &'a str, nodes: &'a [AstNode], spans: &[NodeSpan]) -> Vec<Self> {
+        nodes
+            .iter()
+            .zip(spans)
+            .map(|(ast, span)| {
+                let (commands_span, commands) = commands(src, span.span);
+                Self {
+                    ast,
+                    span: span.span,
+                    commands,
+                    commands_span,
+                    body: match ast {
+                        AstNode::Loop(body) => Self::zip(src, body, &span.body),
+                        _ => Vec::new(),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// The part of a node's source after any clearing loop merged into it.
+fn commands(src: &str, span: Span) -> (Span, &[u8]) {
+    let text = &src.as_bytes()[span.start..span.end];
+    let start = text.iter().rposition(|&c| c == b']').map_or(0, |i| i + 1);
+    let span = Span {
+        start: span.start + start,
+        end: span.end,
+    };
+    (span, &text[start..])
+}
+
+/// What is known about the tape at some point, relative to a frame that is
+/// cell 0 for as long as `absolute` holds.
+#[derive(Clone)]
+struct State {
+    pointer: isize,
+    absolute: bool,
+    known: HashMap<isize, u8>,
+    /// Whether every cell outside of `touched` still holds its initial zero.
+    fresh: bool,
+    touched: HashSet<isize>,
+}
+impl State {
+    fn start() -> Self {
+        Self {
+            pointer: 0,
+            absolute: true,
+            known: HashMap::new(),
+            fresh: true,
+            touched: HashSet::new(),
+        }
+    }
+
+    fn value(&self) -> Option<u8> {
+        match self.known.get(&self.pointer) {
+            Some(&value) => Some(value),
+            None => (self.fresh && !self.touched.contains(&self.pointer)).then_some(0),
+        }
+    }
+    fn set(&mut self, value: Option<u8>) {
+        self.forget(self.pointer);
+        if let Some(value) = value {
+            self.known.insert(self.pointer, value);
+        }
+    }
+    fn forget(&mut self, cell: isize) {
+        self.known.remove(&cell);
+        self.touched.insert(cell);
+    }
+    fn forget_all(&mut self) {
+        self.known.clear();
+        self.fresh = false;
+        self.touched.clear();
+    }
+    /// Forgets what a loop body may have changed, see [`writes`].
+    fn forget_writes(&mut self, balanced: bool, written: &HashSet<isize>) {
+        if balanced {
+            for &offset in written {
+                self.forget(self.pointer + offset);
+            }
+        } else {
+            self.absolute = false;
+            self.forget_all();
+        }
+    }
+}
+
+/// Lints a block, returning whether it provably never finishes.
+fn block(nodes: &[Node], state: &mut State, lints: &mut Vec<Lint>) -> bool {
+    for (i, node) in nodes.iter().enumerate() {
+        match *node.ast {
+            AstNode::Modify(amount) => {
+                cancelling(node, ('+', '-'), lints);
+                state.set(state.value().map(|v| v.wrapping_add_signed(amount)));
+            }
+            AstNode::Set(value) => {
+                cancelling(node, ('+', '-'), lints);
+                state.set(Some(value));
+            }
+            AstNode::Move(amount) => {
+                cancelling(node, ('>', '<'), lints);
+                if state.absolute && state.pointer + lowest(node.commands) < 0 {
+                    let message =
+                        format!("pointer moves left of cell 0 from cell {}", state.pointer);
+                    push(lints, LintKind::NegativePointer, node.span, message);
+                    state.absolute = false;
+                }
+                state.pointer += amount;
+            }
+            AstNode::Output | AstNode::Dump | AstNode::Data(_) => (),
+            AstNode::Input => state.set(None),
+            AstNode::Loop(_) => {
+                let condition = state.value();
+                if condition == Some(0) {
+                    let message = "loop never runs, its cell is always zero here".to_owned();
+                    push(lints, LintKind::DeadLoop, node.span, message);
+                    continue;
+                }
+
+                let stuck = is_stuck(&node.body);
+                if stuck {
+                    let message =
+                        "loop never ends once entered, it neither moves nor changes its cell"
+                            .to_owned();
+                    push(lints, LintKind::InfiniteLoop, node.span, message);
+                }
+
+                // A loop that is entered and never gets through its first
+                // iteration only ever sees the tape as it is now.
+                if condition.is_some() {
+                    let mut first = state.clone();
+                    let mut first_lints = Vec::new();
+                    if block(&node.body, &mut first, &mut first_lints) || stuck {
+                        lints.append(&mut first_lints);
+                        if let (Some(first), Some(last)) = (nodes.get(i + 1), nodes.last()) {
+                            let message = "unreachable, the loop before it never ends".to_owned();
+                            push(
+                                lints,
+                                LintKind::UnreachableCode,
+                                first.span.to(last.span),
+                                message,
+                            );
+                        }
+                        return true;
+                    }
+                }
+
+                // Otherwise the body also runs on whatever the iterations
+                // before left behind.
+                let mut written = HashSet::new();
+                let balanced = writes(&node.body, 0, &mut written);
+                let mut inner = state.clone();
+                inner.forget_writes(balanced, &written);
+                block(&node.body, &mut inner, lints);
+
+                state.forget_writes(balanced, &written);
+                state.set(Some(0));
+            }
+        }
+    }
+    false
+}
+
+/// Flags a run of commands that goes both ways.
+fn cancelling(node: &Node, (up, down): (char, char), lints: &mut Vec<Lint>) {
+    let count = |c| node.commands.iter().filter(|&&d| d == c as u8).count();
+    let (ups, downs) = (count(up), count(down));
+    if ups == 0 || downs == 0 {
+        return;
+    }
+    let amount = ups as isize - downs as isize;
+    let c = if amount < 0 { down } else { up };
+    let message = match amount {
+        0 => format!("these {up}{down} commands cancel out"),
+        _ => format!(
+            "these {up}{down} commands amount to {}",
+            c.to_string().repeat(amount.unsigned_abs())
+        ),
+    };
+    push(
+        lints,
+        LintKind::CancellingCommands,
+        node.commands_span,
+        message,
+    );
+}
+fn push(lints: &mut Vec<Lint>, kind: LintKind, span: Span, message: String) {
+    lints.push(Lint {
+        kind,
+        span,
+        message,
+    });
+}
+/// How far left of its start a run of `<` and `>` reaches.
+fn lowest(commands: &[u8]) -> isize {
+    let mut offset = 0isize;
+    let mut lowest = 0;
+    for &c in commands {
+        match c {
+            b'>' => offset += 1,
+            b'<' => offset -= 1,
+            _ => (),
+        }
+        lowest = lowest.min(offset);
+    }
+    lowest
+}
+
+/// Whether a loop body can never move the pointer or change its cell.
+/// Whether a loop with this body never ends once entered: every iteration
+/// brings the pointer back to the loop's cell without changing it. A body
+/// holding a loop that is stuck itself is reported there instead.
+fn is_stuck(body: &[Node]) -> bool {
+    let mut offset = 0;
+    !may_leave(body, &mut offset) && offset == 0
+}
+
+/// Walks `nodes` from `offset`, returning whether they may change the cell at
+/// offset zero or move by an amount that is not known.
+fn may_leave(nodes: &[Node], offset: &mut isize) -> bool {
+    for node in nodes {
+        match *node.ast {
+            AstNode::Modify(0) | AstNode::Output | AstNode::Dump | AstNode::Data(_) => (),
+            AstNode::Modify(_) | AstNode::Set(_) | AstNode::Input => {
+                if *offset == 0 {
+                    return true;
+                }
+            }
+            AstNode::Move(amount) => *offset += amount,
+            AstNode::Loop(_) => {
+                let start = *offset;
+                if is_stuck(&node.body) || may_leave(&node.body, offset) || *offset != start {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Collects the offsets of the cells a block may change, returning whether the
+/// pointer always ends where it started.
+fn writes(nodes: &[Node], mut offset: isize, written: &mut HashSet<isize>) -> bool {
+    let start = offset;
+    for node in nodes {
+        match *node.ast {
+            AstNode::Modify(_) | AstNode::Set(_) | AstNode::Input => {
+                written.insert(offset);
+            }
+            AstNode::Move(amount) => offset += amount,
+            AstNode::Output | AstNode::Dump | AstNode::Data(_) => (),
+            AstNode::Loop(_) => {
+                if !writes(&node.body, offset, written) {
+                    return false;
+                }
+            }
+        }
+    }
+    offset == start
+}
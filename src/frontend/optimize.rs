@@ -1,15 +1,102 @@
-use super::expr_tree::{BoundsRange, CellOffset, Instruction, Program};
+use super::{
+    expr_tree::{BoundsRange, CellOffset, Instruction, Program},
+    partial_eval::eval_constant_prefix,
+    printing::pretty_print,
+};
+use crate::{
+    ir::limits::Limits,
+    pass::{OptLevel, PassManager, PassTarget},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+};
+
+pub const PREFIX_EVAL_BUDGET: usize = 1 << 20;
+pub const UNROLL_BUDGET: usize = 64;
 
 pub fn apply_optimizations(program: &mut Program) {
-    normalize_pointer_movement(program);
-    remove_dead(program);
-    mark_balanced_blocks(program);
-    merge_verifications(program);
-    remove_dead_verifications(program);
-    recog_additions(program);
-    remove_dead_if_statements(program);
-    merge_verifications(program);
-    remove_dead_verifications(program);
+    pass_manager(OptLevel::O2).run(program);
+}
+
+type FrontendPass = (&'static str, fn(&mut Program));
+
+pub fn pass_manager(level: OptLevel) -> PassManager<Program> {
+    let passes: &[FrontendPass] = match level {
+        OptLevel::O0 => &[],
+        OptLevel::O1 => &[
+            ("normalize-pointer-movement", normalize_pointer_movement),
+            ("remove-dead", remove_dead),
+            ("mark-balanced-blocks", mark_balanced_blocks),
+            ("merge-verifications", merge_verifications),
+            ("remove-dead-verifications", remove_dead_verifications),
+            ("recog-additions", recog_additions),
+            ("remove-dead-if-statements", remove_dead_if_statements),
+            ("merge-verifications", merge_verifications),
+            ("remove-dead-verifications", remove_dead_verifications),
+            ("allocate-static-tape", allocate_static_tape),
+        ],
+        OptLevel::O2 => &[
+            ("normalize-pointer-movement", normalize_pointer_movement),
+            ("remove-dead", remove_dead),
+            ("mark-balanced-blocks", mark_balanced_blocks),
+            ("remove-dead-loops", remove_dead_loops),
+            ("merge-verifications", merge_verifications),
+            ("remove-dead-verifications", remove_dead_verifications),
+            ("recog-additions", recog_additions),
+            ("remove-dead-if-statements", remove_dead_if_statements),
+            ("fold-known-cells", fold_known_cells),
+            ("unroll-constant-loops", |p| {
+                unroll_constant_loops(p, UNROLL_BUDGET)
+            }),
+            ("eval-constant-prefix", |p| {
+                eval_constant_prefix(p, PREFIX_EVAL_BUDGET)
+            }),
+            ("normalize-pointer-movement", normalize_pointer_movement),
+            ("remove-dead", remove_dead),
+            ("remove-dead-loops", remove_dead_loops),
+            ("merge-verifications", merge_verifications),
+            ("remove-dead-verifications", remove_dead_verifications),
+            ("allocate-static-tape", allocate_static_tape),
+        ],
+    };
+
+    let mut manager = PassManager::new();
+    for &(name, pass) in passes {
+        manager.add_pass(name, move |p: &mut Program| {
+            let before = p.clone();
+            pass(p);
+            *p != before
+        });
+    }
+    manager
+}
+/// Like [`pass_manager`], but when `limits` cap the tape size, leaves it
+/// growing as the program goes and keeps bounds checks behind the output
+/// before them, so that a program hits the cap where it would without
+/// optimizations instead of before it starts.
+pub fn limited_pass_manager(level: OptLevel, limits: Limits) -> PassManager<Program> {
+    if limits.max_tape.is_none() {
+        return pass_manager(level);
+    }
+
+    let mut manager = pass_manager(level);
+    manager.replace_pass("merge-verifications", |p: &mut Program| {
+        let before = p.clone();
+        merge_verifications_between_io(p);
+        *p != before
+    });
+    manager.disable("eval-constant-prefix");
+    manager.disable("allocate-static-tape");
+    manager
+}
+impl PassTarget for Program {
+    fn instruction_count(&self) -> usize {
+        instruction_count(&self.0)
+    }
+    fn dump(&self, out: &mut dyn Write) -> io::Result<()> {
+        pretty_print(self, out)
+    }
 }
 
 pub fn normalize_pointer_movement(program: &mut Program) {
@@ -28,8 +115,13 @@ fn normalize_pointer_rec(i: &mut Vec<Instruction>, mut offset: isize) {
             Instruction::Output(cell) => *cell += offset,
             Instruction::Input(cell) => *cell += offset,
             Instruction::Set(cell, _) => *cell += offset,
+            Instruction::Print(_) => (),
+            Instruction::Dump(cell) => *cell += offset,
             Instruction::AddMultiple {
                 base, target: cell, ..
+            }
+            | Instruction::Copy {
+                base, target: cell, ..
             } => {
                 *base += offset;
                 *cell += offset;
@@ -65,7 +157,10 @@ fn remove_dead_rec(i: &mut Vec<Instruction>) {
         Output(_) => true,
         Input(_) => true,
         Set(_, _) => true,
+        Print(bytes) => !bytes.is_empty(),
+        Dump(_) => true,
         AddMultiple { .. } => true,
+        Copy { .. } => true,
 
         BoundsCheck(_) => true,
 
@@ -75,7 +170,7 @@ fn remove_dead_rec(i: &mut Vec<Instruction>) {
         }
         If(_, _, body) => {
             remove_dead_rec(body);
-            body.len() != 0
+            !body.is_empty()
         }
     });
 }
@@ -99,9 +194,14 @@ fn mark_bal_blocks_rec(i: &mut Vec<Instruction>) {
 }
 
 pub fn merge_verifications(p: &mut Program) {
-    merge_verif_rec(&mut p.0)
+    merge_verif_rec(&mut p.0, false)
 }
-fn merge_verif_rec(instructions: &mut Vec<Instruction>) {
+/// Like [`merge_verifications`], but never moves a check ahead of input or
+/// output, so a failing check leaves the I/O before it done.
+pub fn merge_verifications_between_io(p: &mut Program) {
+    merge_verif_rec(&mut p.0, true)
+}
+fn merge_verif_rec(instructions: &mut Vec<Instruction>, keep_io_order: bool) {
     let mut insertions = Vec::new();
     let mut insert_index = 0;
     let mut insert_value: Option<BoundsRange> = None;
@@ -117,13 +217,20 @@ fn merge_verif_rec(instructions: &mut Vec<Instruction>) {
                 }
             }
             &mut Loop(bal, _, ref mut body) | &mut If(bal, _, ref mut body) => {
-                if !bal {
+                let io = keep_io_order && body.iter().any(Instruction::performs_io);
+                if !bal || io {
                     if let Some(val) = insert_value.take() {
                         insertions.push((insert_index, val));
                     }
                     insert_index = i + 1;
                 }
-                merge_verif_rec(body);
+                merge_verif_rec(body, keep_io_order);
+            }
+            ref io if keep_io_order && io.performs_io() => {
+                if let Some(val) = insert_value.take() {
+                    insertions.push((insert_index, val));
+                }
+                insert_index = i + 1;
             }
             _ => (),
         }
@@ -163,7 +270,7 @@ fn remove_dead_verify_rec(i: &mut Vec<Instruction>, verified: &mut Option<Bounds
                 }
             }
             If(_, _, body) | Loop(_, _, body) => {
-                remove_dead_verify_rec(body, verified);
+                remove_dead_verify_rec(body, &mut verified.clone());
                 true
             }
             _ => true,
@@ -176,42 +283,239 @@ fn remove_dead_verify_rec(i: &mut Vec<Instruction>, verified: &mut Option<Bounds
     })
 }
 
+/// Replaces every bounds check with a single one at the start of the program
+/// when [`tape_extent`] knows every cell it can reach.
+pub fn allocate_static_tape(p: &mut Program) {
+    let Some(extent) = tape_extent(p) else {
+        return;
+    };
+    if extent.start < 0 {
+        return;
+    }
+    remove_bounds_checks(&mut p.0);
+    p.0.insert(0, Instruction::BoundsCheck(extent));
+}
+fn remove_bounds_checks(instructions: &mut Vec<Instruction>) {
+    instructions.retain_mut(|i| match i {
+        Instruction::BoundsCheck(_) => false,
+        Instruction::Loop(_, _, body) | Instruction::If(_, _, body) => {
+            remove_bounds_checks(body);
+            true
+        }
+        _ => true,
+    });
+}
+
+/// The range of cells covered by the program's bounds checks, relative to the
+/// initial pointer, or `None` if a loop can move the pointer arbitrarily far
+/// or there are no checks at all.
+pub fn tape_extent(p: &Program) -> Option<BoundsRange> {
+    let mut extent = None;
+    pointer_range(&p.0, (0, 0), &mut extent)?;
+    extent
+}
+/// Widens `extent` by every check in `instructions` for a pointer anywhere in
+/// `pointer`, returning where the pointer can be afterwards.
+fn pointer_range(
+    instructions: &[Instruction],
+    mut pointer: (isize, isize),
+    extent: &mut Option<BoundsRange>,
+) -> Option<(isize, isize)> {
+    for i in instructions {
+        use Instruction::*;
+        match *i {
+            Move(amount) => pointer = (pointer.0 + amount, pointer.1 + amount),
+            BoundsCheck(bounds) => {
+                let spread = (pointer.1 - pointer.0) as usize;
+                let checked = BoundsRange {
+                    start: pointer.0 + bounds.start,
+                    length: bounds.length + spread,
+                };
+                *extent = Some(extent.map_or(checked, |e| e.merge(checked)));
+            }
+            // Each iteration moves the pointer by the same amounts, so a body
+            // that can leave the range it started in walks off without bound.
+            Loop(_, _, ref body) => {
+                let after = pointer_range(body, pointer, extent)?;
+                if after.0 < pointer.0 || after.1 > pointer.1 {
+                    return None;
+                }
+            }
+            If(_, _, ref body) => {
+                let after = pointer_range(body, pointer, extent)?;
+                pointer = (pointer.0.min(after.0), pointer.1.max(after.1));
+            }
+            _ => (),
+        }
+    }
+    Some(pointer)
+}
+
 pub fn recog_additions(p: &mut Program) {
     p.0.iter_mut().for_each(recog_additions_rec);
 }
 fn recog_additions_rec(i: &mut Instruction) {
-    if let Instruction::Loop(_, base, body) = i {
-        body.iter_mut().for_each(recog_additions_rec);
+    match i {
+        Instruction::Loop(_, base, body) => {
+            body.iter_mut().for_each(recog_additions_rec);
+
+            let linear = LinearLoop::analyze(*base, body).filter(|l| l.step % 2 != 0);
+            if let Some(linear) = linear {
+                *i = Instruction::If(true, *base, linear.into_multiples());
+            }
+        }
+        Instruction::If(_, _, body) => body.iter_mut().for_each(recog_additions_rec),
+        _ => (),
+    }
+}
 
-        let mut args = Vec::new();
-        let mut decremented = false;
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CellEffect {
+    Add(i8),
+    Set(u8),
+}
+
+struct LinearLoop {
+    base: CellOffset,
+    step: i8,
+    checks: Vec<Instruction>,
+    cells: Vec<(CellOffset, CellEffect)>,
+    additions: Vec<(CellOffset, CellOffset, i8)>,
+}
+impl LinearLoop {
+    fn analyze(base: CellOffset, body: &[Instruction]) -> Option<Self> {
+        let mut checks = Vec::new();
+        let mut cells: Vec<(CellOffset, CellEffect)> = Vec::new();
+        let mut additions = Vec::new();
+        let mut step = 0i8;
+
+        let mut ops = Vec::new();
         for i in body {
-            if let Instruction::Modify(cell, amount) = i {
-                if decremented && cell == base {
-                    return;
-                } else if cell == base && *amount == -1 {
-                    decremented = true;
-                } else {
-                    args.push((*cell, *amount));
+            match i {
+                Instruction::If(_, con, inner) if if_is_clear_addition(inner, *con) => {
+                    ops.extend(inner.iter())
+                }
+                i => ops.push(i),
+            }
+        }
+
+        for i in ops {
+            use Instruction::*;
+            match *i {
+                BoundsCheck(bounds) => checks.push(BoundsCheck(bounds)),
+                Modify(cell, amount) if cell == base => step = step.wrapping_add(amount),
+                Modify(cell, amount) => match effect_mut(&mut cells, cell) {
+                    CellEffect::Add(a) => *a = a.wrapping_add(amount),
+                    CellEffect::Set(v) => *v = v.wrapping_add_signed(amount),
+                },
+                Set(cell, val) if cell != base => {
+                    *effect_mut(&mut cells, cell) = CellEffect::Set(val)
+                }
+                AddMultiple {
+                    target,
+                    base: source,
+                    factor,
+                } if target != base && source != base && target != source => {
+                    // Only the first iteration can see a nonzero source, so the
+                    // source must still be untouched and must be cleared later on.
+                    if effect(&cells, source).is_some() {
+                        return None;
+                    }
+                    additions.push((target, source, factor));
                 }
-            } else {
-                return;
+                _ => return None,
             }
         }
 
-        if decremented {
-            let mut body: Vec<_> = args
-                .into_iter()
-                .map(|(c, a)| Instruction::AddMultiple {
-                    base: *base,
-                    target: c,
-                    factor: a,
-                })
-                .collect();
-            body.push(Instruction::Set(*base, 0));
-            *i = Instruction::If(true, *base, body);
+        for &(target, source, _) in &additions {
+            if effect(&cells, source) != Some(CellEffect::Set(0)) {
+                return None;
+            }
+            if let Some(CellEffect::Set(_)) = effect(&cells, target) {
+                return None;
+            }
         }
+
+        Some(Self {
+            base,
+            step,
+            checks,
+            cells,
+            additions,
+        })
+    }
+
+    fn into_multiples(self) -> Vec<Instruction> {
+        // The loop runs n times where base + n * step == 0 (mod 256),
+        // so n == base * -step^-1 and every addend becomes a multiple of base.
+        let per_base = mod_inverse(self.step).wrapping_neg();
+        let base = self.base;
+        self.into_instructions(|cell, amount| Instruction::AddMultiple {
+            target: cell,
+            base,
+            factor: amount.wrapping_mul(per_base),
+        })
     }
+    fn into_trips(self, trips: u8) -> Vec<Instruction> {
+        self.into_instructions(|cell, amount| {
+            Instruction::Modify(cell, amount.wrapping_mul(trips as i8))
+        })
+    }
+    fn into_instructions(
+        self,
+        mut add: impl FnMut(CellOffset, i8) -> Instruction,
+    ) -> Vec<Instruction> {
+        let mut body = self.checks;
+        for &(cell, effect) in &self.cells {
+            if let CellEffect::Add(amount) = effect {
+                if amount != 0 {
+                    body.push(add(cell, amount));
+                }
+            }
+        }
+        for (target, source, factor) in self.additions {
+            body.push(Instruction::AddMultiple {
+                target,
+                base: source,
+                factor,
+            });
+        }
+        for &(cell, effect) in &self.cells {
+            if let CellEffect::Set(val) = effect {
+                body.push(Instruction::Set(cell, val));
+            }
+        }
+        body.push(Instruction::Set(self.base, 0));
+        body
+    }
+}
+fn if_is_clear_addition(body: &[Instruction], con: CellOffset) -> bool {
+    body.iter().all(|i| match *i {
+        Instruction::BoundsCheck(_) => true,
+        Instruction::AddMultiple { base, .. } => base == con,
+        Instruction::Set(cell, val) => cell == con && val == 0,
+        _ => false,
+    })
+}
+fn effect(cells: &[(CellOffset, CellEffect)], cell: CellOffset) -> Option<CellEffect> {
+    cells.iter().find(|(c, _)| *c == cell).map(|(_, e)| *e)
+}
+fn effect_mut(cells: &mut Vec<(CellOffset, CellEffect)>, cell: CellOffset) -> &mut CellEffect {
+    let index = match cells.iter().position(|(c, _)| *c == cell) {
+        Some(index) => index,
+        None => {
+            cells.push((cell, CellEffect::Add(0)));
+            cells.len() - 1
+        }
+    };
+    &mut cells[index].1
+}
+fn mod_inverse(odd: i8) -> i8 {
+    let mut inverse = odd;
+    for _ in 0..3 {
+        inverse = inverse.wrapping_mul(2i8.wrapping_sub(odd.wrapping_mul(inverse)));
+    }
+    inverse
 }
 
 pub fn remove_dead_if_statements(p: &mut Program) {
@@ -247,12 +551,372 @@ fn remove_dead_if_rec(instructions: &mut Vec<Instruction>) {
 fn if_is_dead(i: &[Instruction], con: CellOffset) -> bool {
     for i in i {
         use Instruction::*;
-        match i {
-            &AddMultiple { base, .. } if base == con => (),
-            &Set(cell, val) if cell == con && val == 0 => (),
+        match *i {
+            AddMultiple { base, .. } if base == con => (),
+            Set(cell, val) if cell == con && val == 0 => (),
             _ => return false,
         }
     }
 
     true
 }
+
+pub fn fold_known_cells(p: &mut Program) {
+    fold_known_rec(&mut p.0, None, false)
+}
+/// Also relies on every cell starting out zero, which only holds for a whole
+/// program.
+pub fn unroll_constant_loops(p: &mut Program, budget: usize) {
+    fold_known_rec(&mut p.0, Some(budget), true)
+}
+fn fold_known_rec(instructions: &mut Vec<Instruction>, unroll_budget: Option<usize>, zeroed: bool) {
+    let mut folder = CellFolder::new(unroll_budget, zeroed);
+    for i in instructions.drain(..) {
+        folder.fold(i);
+    }
+    instructions.extend(folder.out.into_iter().flatten());
+}
+
+struct CellFolder {
+    out: Vec<Option<Instruction>>,
+    known: HashMap<CellOffset, u8>,
+    touched: Option<HashSet<CellOffset>>,
+    pending: HashMap<CellOffset, (Option<u8>, Vec<usize>)>,
+    unroll_budget: Option<usize>,
+}
+impl CellFolder {
+    fn new(unroll_budget: Option<usize>, zeroed: bool) -> Self {
+        Self {
+            out: Vec::new(),
+            known: HashMap::new(),
+            touched: zeroed.then(HashSet::new),
+            pending: HashMap::new(),
+            unroll_budget,
+        }
+    }
+
+    fn fold(&mut self, i: Instruction) {
+        use Instruction::*;
+        match i {
+            Set(cell, val) => self.set(cell, val),
+            Modify(cell, amount) => self.modify(cell, amount),
+            Copy {
+                target,
+                base,
+                factor,
+            } => {
+                self.read(base);
+                if let Some(val) = self.value(base) {
+                    self.set(target, val.wrapping_mul(factor as u8));
+                } else {
+                    self.overwrite(target, i);
+                }
+            }
+            AddMultiple {
+                target,
+                base,
+                factor,
+            } => {
+                self.read(base);
+                if let Some(val) = self.value(base) {
+                    self.modify(target, val.wrapping_mul(factor as u8) as i8);
+                } else if self.value(target) == Some(0) {
+                    self.overwrite(
+                        target,
+                        Copy {
+                            target,
+                            base,
+                            factor,
+                        },
+                    );
+                } else {
+                    self.push_pending(target, self.value(target), i);
+                    self.forget(target);
+                }
+            }
+            Output(cell) => {
+                self.read(cell);
+                self.out.push(Some(i));
+            }
+            Input(cell) => {
+                self.read(cell);
+                self.forget(cell);
+                self.out.push(Some(i));
+            }
+            BoundsCheck(_) | Print(_) => self.out.push(Some(i)),
+            Dump(_) => {
+                self.pending.clear();
+                self.out.push(Some(i));
+            }
+            Move(_) => {
+                self.forget_all();
+                self.pending.clear();
+                self.out.push(Some(i));
+            }
+            Loop(_, cell, _) | If(_, cell, _) if self.value(cell) == Some(0) => (),
+            Loop(bal, cell, mut body) => {
+                if let Some(unrolled) = self.unroll(bal, cell, &body) {
+                    unrolled.into_iter().for_each(|i| self.fold(i));
+                    return;
+                }
+                fold_known_rec(&mut body, self.unroll_budget, false);
+                self.after_block(bal, &body);
+                self.learn(cell, 0);
+                self.out.push(Some(Loop(bal, cell, body)));
+            }
+            If(bal, cell, mut body) => {
+                fold_known_rec(&mut body, self.unroll_budget, false);
+                self.after_block(bal, &body);
+                self.out.push(Some(If(bal, cell, body)));
+            }
+        }
+    }
+
+    fn set(&mut self, cell: CellOffset, val: u8) {
+        if self.value(cell) == Some(val) {
+            return;
+        }
+        let prior = self.kill_pending(cell);
+        if prior != Some(val) {
+            self.push_pending(cell, prior, Instruction::Set(cell, val));
+        }
+        self.learn(cell, val);
+    }
+    fn modify(&mut self, cell: CellOffset, amount: i8) {
+        if amount == 0 {
+            return;
+        }
+        if let Some(val) = self.value(cell) {
+            self.set(cell, val.wrapping_add_signed(amount));
+        } else {
+            self.push_pending(cell, None, Instruction::Modify(cell, amount));
+        }
+    }
+
+    fn overwrite(&mut self, cell: CellOffset, i: Instruction) {
+        let prior = self.kill_pending(cell);
+        self.push_pending(cell, prior, i);
+        self.forget(cell);
+    }
+    fn kill_pending(&mut self, cell: CellOffset) -> Option<u8> {
+        let Some((prior, dead)) = self.pending.remove(&cell) else {
+            return self.value(cell);
+        };
+        for dead in dead {
+            self.out[dead] = None;
+        }
+        prior
+    }
+    fn push_pending(&mut self, cell: CellOffset, prior: Option<u8>, i: Instruction) {
+        let (_, writes) = self.pending.entry(cell).or_insert((prior, Vec::new()));
+        writes.push(self.out.len());
+        self.out.push(Some(i));
+    }
+    fn read(&mut self, cell: CellOffset) {
+        self.pending.remove(&cell);
+    }
+
+    fn value(&self, cell: CellOffset) -> Option<u8> {
+        match (self.known.get(&cell), &self.touched) {
+            (Some(&val), _) => Some(val),
+            (None, Some(touched)) if !touched.contains(&cell) => Some(0),
+            _ => None,
+        }
+    }
+    fn learn(&mut self, cell: CellOffset, val: u8) {
+        self.known.insert(cell, val);
+        if let Some(touched) = &mut self.touched {
+            touched.insert(cell);
+        }
+    }
+    fn forget(&mut self, cell: CellOffset) {
+        self.known.remove(&cell);
+        if let Some(touched) = &mut self.touched {
+            touched.insert(cell);
+        }
+    }
+    fn forget_all(&mut self) {
+        self.known.clear();
+        self.touched = None;
+    }
+
+    fn unroll(
+        &self,
+        bal: bool,
+        cell: CellOffset,
+        body: &[Instruction],
+    ) -> Option<Vec<Instruction>> {
+        let budget = self.unroll_budget?;
+        let start = self.value(cell).filter(|_| bal)?;
+        let step = constant_step(cell, body)?;
+        let trips = (0..=u8::MAX).find(|&n| start.wrapping_add(n.wrapping_mul(step as u8)) == 0)?;
+
+        if trips as usize * instruction_count(body) <= budget {
+            Some(
+                body.iter()
+                    .cycle()
+                    .take(trips as usize * body.len())
+                    .cloned()
+                    .collect(),
+            )
+        } else {
+            LinearLoop::analyze(cell, body).map(|l| l.into_trips(trips))
+        }
+    }
+
+    fn after_block(&mut self, balanced: bool, body: &[Instruction]) {
+        self.pending.clear();
+        if balanced {
+            let mut written = HashSet::new();
+            written_cells(body, &mut written);
+            written.into_iter().for_each(|cell| self.forget(cell));
+        } else {
+            self.forget_all();
+        }
+    }
+}
+fn constant_step(cell: CellOffset, body: &[Instruction]) -> Option<i8> {
+    let mut step = 0i8;
+    for i in body {
+        use Instruction::*;
+        match *i {
+            Modify(c, amount) if c == cell => step = step.wrapping_add(amount),
+            Set(c, _) | Input(c) | AddMultiple { target: c, .. } | Copy { target: c, .. }
+                if c == cell =>
+            {
+                return None
+            }
+            Move(_) => return None,
+            Loop(_, _, ref inner) | If(_, _, ref inner) => {
+                let mut written = HashSet::new();
+                written_cells(inner, &mut written);
+                if written.contains(&cell) {
+                    return None;
+                }
+            }
+            _ => (),
+        }
+    }
+    Some(step)
+}
+fn instruction_count(body: &[Instruction]) -> usize {
+    body.iter()
+        .map(|i| match i {
+            Instruction::Loop(_, _, inner) | Instruction::If(_, _, inner) => {
+                1 + instruction_count(inner)
+            }
+            _ => 1,
+        })
+        .sum()
+}
+fn written_cells(instructions: &[Instruction], written: &mut HashSet<CellOffset>) {
+    for i in instructions {
+        use Instruction::*;
+        match *i {
+            Modify(cell, _) | Set(cell, _) | Input(cell) => {
+                written.insert(cell);
+            }
+            AddMultiple { target, .. } | Copy { target, .. } => {
+                written.insert(target);
+            }
+            Loop(_, _, ref body) | If(_, _, ref body) => written_cells(body, written),
+            Move(_) | Output(_) | Print(_) | Dump(_) | BoundsCheck(_) => (),
+        }
+    }
+}
+
+pub fn remove_dead_loops(p: &mut Program) {
+    let mut zeros = Zeros::AllExcept(HashSet::new());
+    remove_dead_loops_rec(&mut p.0, &mut zeros);
+}
+fn remove_dead_loops_rec(instructions: &mut Vec<Instruction>, zeros: &mut Zeros) {
+    instructions.retain_mut(|i| {
+        use Instruction::*;
+        match i {
+            &mut Modify(cell, amount) => zeros.set(cell, amount == 0 && zeros.is_zero(cell)),
+            &mut Set(cell, val) => {
+                if val == 0 && zeros.is_zero(cell) {
+                    return false;
+                }
+                zeros.set(cell, val == 0);
+            }
+            &mut Input(cell) => zeros.set(cell, false),
+            &mut AddMultiple { target, base, .. } => {
+                if zeros.is_zero(base) {
+                    return false;
+                }
+                zeros.set(target, false);
+            }
+            &mut Copy { target, base, .. } => zeros.set(target, zeros.is_zero(base)),
+            &mut Move(amount) => zeros.shift(amount),
+            Output(_) | Print(_) | Dump(_) | BoundsCheck(_) => (),
+            &mut Loop(bal, cell, ref mut body) => {
+                if zeros.is_zero(cell) {
+                    return false;
+                }
+                if bal {
+                    let mut written = HashSet::new();
+                    written_cells(body, &mut written);
+                    zeros.forget(&written);
+                    remove_dead_loops_rec(body, &mut zeros.clone());
+                } else {
+                    *zeros = Zeros::Only(HashSet::new());
+                    remove_dead_loops_rec(body, &mut zeros.clone());
+                }
+                zeros.set(cell, true);
+            }
+            &mut If(bal, cell, ref mut body) => {
+                if zeros.is_zero(cell) {
+                    return false;
+                }
+                let mut taken = zeros.clone();
+                remove_dead_loops_rec(body, &mut taken);
+                if bal {
+                    zeros.join(&taken);
+                } else {
+                    *zeros = Zeros::Only(HashSet::new());
+                }
+            }
+        }
+        true
+    });
+}
+
+#[derive(Clone, Debug)]
+enum Zeros {
+    AllExcept(HashSet<CellOffset>),
+    Only(HashSet<CellOffset>),
+}
+impl Zeros {
+    fn is_zero(&self, cell: CellOffset) -> bool {
+        match self {
+            Self::AllExcept(nonzero) => !nonzero.contains(&cell),
+            Self::Only(zero) => zero.contains(&cell),
+        }
+    }
+    fn set(&mut self, cell: CellOffset, is_zero: bool) {
+        match (self, is_zero) {
+            (Self::AllExcept(nonzero), true) => nonzero.remove(&cell),
+            (Self::AllExcept(nonzero), false) => nonzero.insert(cell),
+            (Self::Only(zero), true) => zero.insert(cell),
+            (Self::Only(zero), false) => zero.remove(&cell),
+        };
+    }
+    fn forget(&mut self, cells: &HashSet<CellOffset>) {
+        for &cell in cells {
+            self.set(cell, false);
+        }
+    }
+    fn shift(&mut self, by: isize) {
+        let (Self::AllExcept(cells) | Self::Only(cells)) = self;
+        *cells = cells.drain().map(|c| c - by).collect();
+    }
+    fn join(&mut self, other: &Self) {
+        *self = match (&*self, other) {
+            (Self::AllExcept(a), Self::AllExcept(b)) => Self::AllExcept(a | b),
+            (Self::AllExcept(nonzero), Self::Only(zero))
+            | (Self::Only(zero), Self::AllExcept(nonzero)) => Self::Only(zero - nonzero),
+            (Self::Only(a), Self::Only(b)) => Self::Only(a & b),
+        };
+    }
+}
@@ -1,47 +1,107 @@
 use super::{
     ast::{Ast, AstNode},
-    lexer::Token,
+    lexer::{Span, Token},
 };
+use std::fmt::Display;
 
-pub fn parse(mut src: impl Iterator<Item = Token>) -> Ast {
-    let (mut body, closed) = parse_instructions(&mut src);
-    assert!(!closed);
-
-    if let Some(AstNode::Loop(_)) = body.get(0) {
-        body.remove(0);
+/// Parses a program whose brackets are known to match.
+///
+/// # Panics
+///
+/// On an unmatched `[` or `]`; use [`try_parse`] for untrusted sources.
+pub fn parse(src: impl Iterator<Item = Token>) -> Ast {
+    try_parse(src).unwrap_or_else(|err| panic!("{err}"))
+}
+/// Like [`parse`], but reports unmatched brackets as an error.
+///
+/// A `!` inside a loop turns the rest of the source, including the `]`, into
+/// input, so it leaves that loop unmatched.
+pub fn try_parse(src: impl Iterator<Item = Token>) -> Result<Ast, String> {
+    match parse_spanned(src.map(|tok| (tok, Span::at(0)))) {
+        Ok((ast, _)) => Ok(ast),
+        Err(err) => Err(err.to_string()),
+    }
+}
+/// Like [`try_parse`], but also returns where every node came from.
+pub fn parse_spanned(
+    mut src: impl Iterator<Item = (Token, Span)>,
+) -> Result<(Ast, Vec<NodeSpan>), Unmatched> {
+    let (body, spans, close) = parse_instructions(&mut src)?;
+    if let Some(span) = close {
+        return Err(Unmatched { bracket: ']', span });
     }
+    Ok((Ast(body), spans))
+}
+
+/// The source an [`AstNode`] was parsed from, which for merged nodes covers
+/// all of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeSpan {
+    pub span: Span,
+    /// The spans of a loop's body, one per node.
+    pub body: Vec<NodeSpan>,
+}
 
-    Ast(body)
+/// A `[` without a `]` or the other way around.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Unmatched {
+    pub bracket: char,
+    pub span: Span,
+}
+impl Display for Unmatched {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unmatched {}", self.bracket)
+    }
 }
-fn parse_instructions(src: &mut impl Iterator<Item = Token>) -> (Vec<AstNode>, bool) {
+
+type Parsed = (Vec<AstNode>, Vec<NodeSpan>, Option<Span>);
+type Spanned = (AstNode, NodeSpan);
+
+/// Parses up to the end or a `]`, returning the span of that `]`.
+fn parse_instructions(src: &mut impl Iterator<Item = (Token, Span)>) -> Result<Parsed, Unmatched> {
     let mut i = Vec::new();
+    let mut spans = Vec::new();
     let mut previous = None;
 
     loop {
-        let (tok, closed) = parse_instruction(src);
+        let (tok, close) = parse_instruction(src)?;
         let Some(tok) = tok else {
-            if let Some(prev) = previous.take() {
+            if let Some((prev, span)) = previous.take() {
                 i.push(prev);
+                spans.push(span);
             }
-            return (i, closed);
+            return Ok((i, spans, close));
         };
 
-        if let Some(prev) = previous.take() {
+        if let Some((prev, prev_span)) = previous.take() {
+            let (tok, span) = tok;
             match merge(prev, tok) {
                 Merged::No(prev, tok) => {
                     i.push(prev);
-                    previous = Some(tok);
+                    spans.push(prev_span);
+                    previous = Some((tok, span));
+                }
+                Merged::Yes(tok) => {
+                    let span = NodeSpan {
+                        span: prev_span.span.to(span.span),
+                        body: Vec::new(),
+                    };
+                    previous = Some((tok, span));
                 }
-                Merged::Yes(tok) => previous = Some(tok),
             }
         } else {
             previous = Some(tok);
         }
     }
 }
-fn parse_instruction(src: &mut impl Iterator<Item = Token>) -> (Option<AstNode>, bool) {
-    let Some(tok) = src.next() else { return (None, false) };
+fn parse_instruction(
+    src: &mut impl Iterator<Item = (Token, Span)>,
+) -> Result<(Option<Spanned>, Option<Span>), Unmatched> {
+    let Some((tok, mut span)) = src.next() else {
+        return Ok((None, None));
+    };
 
+    let mut body_spans = Vec::new();
     let i = match tok {
         Token::Plus => AstNode::Modify(1),
         Token::Minus => AstNode::Modify(-1),
@@ -49,27 +109,44 @@ fn parse_instruction(src: &mut impl Iterator<Item = Token>) -> (Option<AstNode>,
         Token::Previous => AstNode::Move(-1),
         Token::Dot => AstNode::Output,
         Token::Comma => AstNode::Input,
-        Token::Close => return (None, true),
+        Token::Dump => AstNode::Dump,
+        Token::EndOfCode => AstNode::Data(
+            src.map(|(tok, data)| {
+                span = span.to(data);
+                match tok {
+                    Token::Data(byte) => byte,
+                    _ => unreachable!("only data follows the end of the code"),
+                }
+            })
+            .collect(),
+        ),
+        Token::Data(_) => unreachable!("data only follows the end of the code"),
+        Token::Close => return Ok((None, Some(span))),
         Token::Open => {
-            let (body, closed) = parse_instructions(src);
-            assert!(closed);
+            let (body, spans, close) = parse_instructions(src)?;
+            let Some(close) = close else {
+                return Err(Unmatched { bracket: '[', span });
+            };
+            span = span.to(close);
             if loop_is_clear(&body) {
                 AstNode::Set(0)
             } else {
+                body_spans = spans;
                 AstNode::Loop(body)
             }
         }
     };
 
-    (Some(i), false)
+    let span = NodeSpan {
+        span,
+        body: body_spans,
+    };
+    Ok((Some((i, span)), None))
 }
 
 fn loop_is_clear(body: &[AstNode]) -> bool {
     if body.len() == 1 {
-        match &body[0] {
-            AstNode::Modify(a) if *a % 2 != 0 => true,
-            _ => false,
-        }
+        matches!(&body[0], AstNode::Modify(a) if *a % 2 != 0)
     } else {
         false
     }
@@ -0,0 +1,174 @@
+use super::expr_tree::{BoundsRange, CellOffset, Instruction, Program};
+
+pub fn eval_constant_prefix(program: &mut Program, budget: usize) {
+    let mut eval = PrefixEval::new(budget);
+    let mut done = 0;
+
+    for i in &program.0 {
+        let mark = eval.mark();
+        if eval.exec(i).is_err() {
+            eval.undo(mark);
+            break;
+        }
+        done += 1;
+    }
+
+    if done == 0 {
+        return;
+    }
+
+    let rest = program.0.split_off(done);
+    program.0 = eval.residual();
+    program.0.extend(rest);
+}
+
+struct PrefixEval {
+    tape: Vec<u8>,
+    pointer: isize,
+    output: Vec<u8>,
+    steps: usize,
+    budget: usize,
+    /// The cells written since the last [`PrefixEval::mark`], with the values
+    /// they held before.
+    written: Vec<(usize, u8)>,
+}
+impl PrefixEval {
+    fn new(budget: usize) -> Self {
+        Self {
+            tape: Vec::new(),
+            pointer: 0,
+            output: Vec::new(),
+            steps: 0,
+            budget,
+            written: Vec::new(),
+        }
+    }
+
+    /// Keeps everything executed so far, returning the state to undo to.
+    fn mark(&mut self) -> (usize, isize, usize) {
+        self.written.clear();
+        (self.tape.len(), self.pointer, self.output.len())
+    }
+    fn undo(&mut self, (tape, pointer, output): (usize, isize, usize)) {
+        for (index, val) in self.written.drain(..).rev() {
+            self.tape[index] = val;
+        }
+        self.tape.truncate(tape);
+        self.pointer = pointer;
+        self.output.truncate(output);
+    }
+
+    fn exec(&mut self, i: &Instruction) -> Result<(), Stop> {
+        self.tick()?;
+
+        use Instruction::*;
+        match *i {
+            Modify(cell, amount) => {
+                let val = self.cell(cell)?.wrapping_add_signed(amount);
+                self.set(cell, val)?;
+            }
+            Move(amount) => self.pointer += amount,
+            Output(cell) => {
+                let val = *self.cell(cell)?;
+                self.output.push(val);
+            }
+            Input(_) => return Err(Stop::NeedsInput),
+            Set(cell, val) => self.set(cell, val)?,
+            Print(ref bytes) => self.output.extend_from_slice(bytes),
+            Dump(_) => return Err(Stop::Dump),
+            AddMultiple {
+                target,
+                base,
+                factor,
+            } => {
+                let addend = self.cell(base)?.wrapping_mul(factor as u8);
+                let val = self.cell(target)?.wrapping_add(addend);
+                self.set(target, val)?;
+            }
+            Copy {
+                target,
+                base,
+                factor,
+            } => {
+                let val = self.cell(base)?.wrapping_mul(factor as u8);
+                self.set(target, val)?;
+            }
+            BoundsCheck(BoundsRange { start, length }) => {
+                if length != 0 {
+                    self.cell(start)?;
+                    self.cell(start + length as isize - 1)?;
+                }
+            }
+            Loop(_, cell, ref body) => {
+                while *self.cell(cell)? != 0 {
+                    self.tick()?;
+                    self.exec_all(body)?;
+                }
+            }
+            If(_, cell, ref body) => {
+                if *self.cell(cell)? != 0 {
+                    self.exec_all(body)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+    fn tick(&mut self) -> Result<(), Stop> {
+        self.steps += 1;
+        if self.steps > self.budget {
+            return Err(Stop::OutOfFuel);
+        }
+        Ok(())
+    }
+    fn exec_all(&mut self, body: &[Instruction]) -> Result<(), Stop> {
+        body.iter().try_for_each(|i| self.exec(i))
+    }
+
+    fn cell(&mut self, offset: CellOffset) -> Result<&mut u8, Stop> {
+        let index = self.pointer + offset;
+        if index < 0 {
+            return Err(Stop::OutOfBounds);
+        }
+        let index = index as usize;
+        if index >= self.tape.len() {
+            self.tape.resize(index + 1, 0);
+        }
+        Ok(&mut self.tape[index])
+    }
+    fn set(&mut self, offset: CellOffset, val: u8) -> Result<(), Stop> {
+        let old = std::mem::replace(self.cell(offset)?, val);
+        self.written.push(((self.pointer + offset) as usize, old));
+        Ok(())
+    }
+
+    fn residual(self) -> Vec<Instruction> {
+        let mut body = Vec::new();
+        if !self.tape.is_empty() {
+            body.push(Instruction::BoundsCheck(BoundsRange {
+                start: 0,
+                length: self.tape.len(),
+            }));
+        }
+        for (cell, &val) in self.tape.iter().enumerate() {
+            if val != 0 {
+                body.push(Instruction::Set(cell as isize, val));
+            }
+        }
+        if !self.output.is_empty() {
+            body.push(Instruction::Print(self.output));
+        }
+        if self.pointer != 0 {
+            body.push(Instruction::Move(self.pointer));
+        }
+        body
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Stop {
+    NeedsInput,
+    Dump,
+    OutOfFuel,
+    OutOfBounds,
+}
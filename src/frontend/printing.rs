@@ -34,11 +34,18 @@ pub fn print_instruction<O: Write>(
         Output(cell) => writeln!(out, "write(stdout, {})", Cell(*cell))?,
         Input(cell) => writeln!(out, "{} = read(stdin)", Cell(*cell))?,
         Set(cell, value) => writeln!(out, "{} = {value}", Cell(*cell))?,
+        Print(bytes) => writeln!(out, "write(stdout, \"{}\")", bytes.escape_ascii())?,
+        Dump(cell) => writeln!(out, "dump({})", Cell(*cell))?,
         AddMultiple {
             base,
             target: cell,
             factor,
         } => writeln!(out, "{} += {} * {}", Cell(*cell), Cell(*base), factor)?,
+        Copy {
+            base,
+            target: cell,
+            factor,
+        } => writeln!(out, "{} = {} * {}", Cell(*cell), Cell(*base), factor)?,
 
         &BoundsCheck(BoundsRange { start, length }) => writeln!(
             out,
@@ -69,7 +76,7 @@ pub fn print_instruction<O: Write>(
     Ok(())
 }
 
-struct Cell(isize);
+pub(super) struct Cell(pub isize);
 impl Display for Cell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let cell = self.0;
@@ -0,0 +1,151 @@
+use super::{expr_tree::CellOffset, printing::Cell};
+use crate::ir::{block::BlockID, profile::Profile};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+pub const HOTTEST_LOOPS: usize = 10;
+
+/// Which `expr_tree` loop every block of a generated module belongs to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoopMap {
+    pub loops: Vec<LoopInfo>,
+    /// The innermost loop around each block, indexed by [`BlockID`].
+    pub blocks: Vec<Option<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoopInfo {
+    /// The indices leading to the loop through the nested instruction lists.
+    pub path: Vec<usize>,
+    pub parent: Option<usize>,
+    pub condition: CellOffset,
+    /// Entered once per iteration.
+    pub body: BlockID,
+}
+impl LoopInfo {
+    pub fn name(&self) -> String {
+        let path: Vec<_> = self.path.iter().map(|i| i.to_string()).collect();
+        format!("loop_{}", path.join("."))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoopStats {
+    pub iterations: u64,
+    /// Instructions run directly in the loop's own blocks.
+    pub self_instructions: u64,
+    /// Instructions run in the loop and every loop nested in it.
+    pub total_instructions: u64,
+}
+
+impl LoopMap {
+    /// The loops around `block`, outermost first.
+    pub fn stack(&self, block: BlockID) -> Vec<usize> {
+        let mut stack = Vec::new();
+        let mut current = self.blocks[usize::from(block)];
+        while let Some(id) = current {
+            stack.push(id);
+            current = self.loops[id].parent;
+        }
+        stack.reverse();
+        stack
+    }
+
+    pub fn stats(&self, profile: &Profile) -> Vec<LoopStats> {
+        let mut stats: Vec<_> = self
+            .loops
+            .iter()
+            .map(|l| LoopStats {
+                iterations: profile.block_entries(l.body),
+                ..LoopStats::default()
+            })
+            .collect();
+
+        for (block, &innermost) in self.blocks.iter().enumerate() {
+            let Some(innermost) = innermost else { continue };
+            let count = profile.block_instructions(BlockID::from(block));
+            stats[innermost].self_instructions += count;
+            let mut current = Some(innermost);
+            while let Some(id) = current {
+                stats[id].total_instructions += count;
+                current = self.loops[id].parent;
+            }
+        }
+        stats
+    }
+}
+
+/// Writes the hottest loops and overall counts in a human readable table.
+pub fn write_report(mut out: impl Write, loops: &LoopMap, profile: &Profile) -> io::Result<()> {
+    let total = profile.total_instructions();
+    writeln!(out, "instructions executed: {total}")?;
+    write!(out, "output bytes: {}", profile.output_bytes)?;
+    match profile.instructions_per_output_byte() {
+        Some(per_byte) => writeln!(out, " ({per_byte:.1} instructions per byte)")?,
+        None => writeln!(out)?,
+    }
+    writeln!(
+        out,
+        "tape high-water mark: {} cells",
+        profile.tape_high_water
+    )?;
+
+    let stats = loops.stats(profile);
+    let mut hottest: Vec<_> = (0..stats.len())
+        .filter(|&id| stats[id].iterations != 0)
+        .collect();
+    hottest.sort_by_key(|&id| std::cmp::Reverse(stats[id].total_instructions));
+    hottest.truncate(HOTTEST_LOOPS);
+    if hottest.is_empty() {
+        return Ok(());
+    }
+
+    let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+    writeln!(out, "\nhottest loops:")?;
+    writeln!(
+        out,
+        "{:>8} {:>8} {:>12}  loop",
+        "total", "self", "iterations"
+    )?;
+    for id in hottest {
+        let (info, stats) = (&loops.loops[id], stats[id]);
+        writeln!(
+            out,
+            "{:>7.1}% {:>7.1}% {:>12}  {} while {} != 0",
+            percent(stats.total_instructions),
+            percent(stats.self_instructions),
+            stats.iterations,
+            info.name(),
+            Cell(info.condition),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes one `main;loop_0;loop_0.2 <instructions>` line per loop nest, the
+/// folded format flamegraph tools read.
+pub fn write_folded(mut out: impl Write, loops: &LoopMap, profile: &Profile) -> io::Result<()> {
+    let mut folded = BTreeMap::new();
+    for block in 0..loops.blocks.len() {
+        let block = BlockID::from(block);
+        let count = profile.block_instructions(block);
+        if count == 0 {
+            continue;
+        }
+        let mut frames = vec!["main".to_owned()];
+        frames.extend(
+            loops
+                .stack(block)
+                .into_iter()
+                .map(|id| loops.loops[id].name()),
+        );
+        *folded.entry(frames.join(";")).or_insert(0) += count;
+    }
+
+    for (stack, count) in folded {
+        writeln!(out, "{stack} {count}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use super::ast::{Ast, AstNode};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<u8>,
+    pub tape: Vec<u8>,
+    pub steps: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Trap {
+    OutOfFuel,
+    NegativePointer,
+}
+
+pub fn interpret(ast: &Ast, input: &[u8], fuel: usize) -> Result<Outcome, Trap> {
+    let mut interp = Interpreter {
+        tape: Vec::new(),
+        pointer: 0,
+        input,
+        output: Vec::new(),
+        steps: 0,
+        fuel,
+    };
+    interp.run(&ast.0)?;
+
+    let len = interp
+        .tape
+        .iter()
+        .rposition(|&c| c != 0)
+        .map_or(0, |i| i + 1);
+    interp.tape.truncate(len);
+    Ok(Outcome {
+        output: interp.output,
+        tape: interp.tape,
+        steps: interp.steps,
+    })
+}
+
+struct Interpreter<'a> {
+    tape: Vec<u8>,
+    pointer: isize,
+    input: &'a [u8],
+    output: Vec<u8>,
+    steps: usize,
+    fuel: usize,
+}
+impl Interpreter<'_> {
+    fn run(&mut self, nodes: &[AstNode]) -> Result<(), Trap> {
+        for node in nodes {
+            self.tick()?;
+            match node {
+                &AstNode::Modify(amount) => {
+                    let cell = self.cell()?;
+                    *cell = cell.wrapping_add_signed(amount);
+                }
+                &AstNode::Move(amount) => self.pointer += amount,
+                AstNode::Output => {
+                    let val = *self.cell()?;
+                    self.output.push(val);
+                }
+                AstNode::Input => {
+                    if let Some((&byte, rest)) = self.input.split_first() {
+                        *self.cell()? = byte;
+                        self.input = rest;
+                    } else {
+                        self.cell()?;
+                    }
+                }
+                &AstNode::Set(val) => *self.cell()? = val,
+                AstNode::Dump | AstNode::Data(_) => (),
+                AstNode::Loop(body) => {
+                    while *self.cell()? != 0 {
+                        self.run(body)?;
+                        self.tick()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tick(&mut self) -> Result<(), Trap> {
+        self.steps += 1;
+        if self.steps > self.fuel {
+            return Err(Trap::OutOfFuel);
+        }
+        Ok(())
+    }
+
+    fn cell(&mut self) -> Result<&mut u8, Trap> {
+        if self.pointer < 0 {
+            return Err(Trap::NegativePointer);
+        }
+        let index = self.pointer as usize;
+        if index >= self.tape.len() {
+            self.tape.resize(index + 1, 0);
+        }
+        Ok(&mut self.tape[index])
+    }
+}
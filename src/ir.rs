@@ -8,13 +8,23 @@ use std::ops::{Index, IndexMut};
 
 pub mod block;
 pub mod builder;
+pub mod bytecode;
+pub mod cfg;
+pub mod dot;
 pub mod exec;
 pub mod instruction;
+pub mod limits;
 pub mod optimize;
 pub mod printing;
+pub mod profile;
 pub mod register;
+pub mod snapshot;
+pub mod trace;
 pub mod types;
+pub mod vm;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
     entry: Option<BlockID>,
     blocks: Vec<Block>,
@@ -33,7 +43,7 @@ impl Module {
         self.entry = Some(entry);
     }
     pub fn add_block(&mut self) -> BlockID {
-        add_with_index(&mut self.blocks, |id| Block::new(id))
+        add_with_index(&mut self.blocks, Block::new)
     }
     pub fn block(&self, id: BlockID) -> Option<&Block> {
         self.blocks.get(id.0)
@@ -62,6 +72,11 @@ impl Module {
         self.entry.unwrap()
     }
 }
+impl Default for Module {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Index<BlockID> for Module {
     type Output = Block;
     fn index(&self, index: BlockID) -> &Self::Output {
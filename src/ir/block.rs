@@ -3,6 +3,7 @@ use std::fmt::Display;
 use super::{instruction::Instruction, register::RegisterID};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub(super) id: BlockID,
     pub(super) body: Vec<Instruction>,
@@ -42,12 +43,18 @@ impl Block {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockID(pub(super) usize);
 impl From<usize> for BlockID {
     fn from(value: usize) -> Self {
         Self(value)
     }
 }
+impl From<BlockID> for usize {
+    fn from(value: BlockID) -> Self {
+        value.0
+    }
+}
 impl Display for BlockID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "@{}", self.0)
@@ -1,6 +1,6 @@
 use super::{
     block::BlockID,
-    instruction::{BinaryOp, Instruction, LeafExpr, TargetBlock, TestOp, UnaryOp},
+    instruction::{BinaryOp, CastOp, Instruction, LeafExpr, TargetBlock, TestOp, UnaryOp},
     register::RegisterID,
     types::Type,
     Module,
@@ -112,6 +112,15 @@ impl<'a> Builder<'a> {
     pub fn xor(&mut self, a: impl Into<LeafExpr>, b: impl Into<LeafExpr>) -> RegisterID {
         self.binop(BinaryOp::Xor, a, b)
     }
+    pub fn shl(&mut self, a: impl Into<LeafExpr>, b: impl Into<LeafExpr>) -> RegisterID {
+        self.binop(BinaryOp::Shl, a, b)
+    }
+    pub fn lshr(&mut self, a: impl Into<LeafExpr>, b: impl Into<LeafExpr>) -> RegisterID {
+        self.binop(BinaryOp::LShr, a, b)
+    }
+    pub fn ashr(&mut self, a: impl Into<LeafExpr>, b: impl Into<LeafExpr>) -> RegisterID {
+        self.binop(BinaryOp::AShr, a, b)
+    }
 
     pub fn unop(&mut self, op: UnaryOp, a: impl Into<LeafExpr>) -> RegisterID {
         let a = a.into();
@@ -143,6 +152,27 @@ impl<'a> Builder<'a> {
         target
     }
 
+    pub fn cast(&mut self, op: CastOp, a: impl Into<LeafExpr>, to: Type) -> RegisterID {
+        let a = a.into();
+        let at = a.expr_type(self.module);
+        match op {
+            CastOp::ZExt | CastOp::SExt => assert!(at.bits() <= to.bits()),
+            CastOp::Trunc => assert!(at.bits() >= to.bits()),
+        }
+        let target = self.add_register(to);
+        self.push_instruction(Instruction::Assign(target, Expr::Cast(a, op, to)));
+        target
+    }
+    pub fn zext(&mut self, a: impl Into<LeafExpr>, to: Type) -> RegisterID {
+        self.cast(CastOp::ZExt, a, to)
+    }
+    pub fn sext(&mut self, a: impl Into<LeafExpr>, to: Type) -> RegisterID {
+        self.cast(CastOp::SExt, a, to)
+    }
+    pub fn trunc(&mut self, a: impl Into<LeafExpr>, to: Type) -> RegisterID {
+        self.cast(CastOp::Trunc, a, to)
+    }
+
     pub fn output(&mut self, value: impl Into<LeafExpr>) {
         self.push_instruction(Instruction::Output(value.into()));
     }
@@ -153,6 +183,12 @@ impl<'a> Builder<'a> {
         target
     }
 
+    pub fn dump(&mut self, index: impl Into<LeafExpr>) {
+        let index = index.into();
+        assert_eq!(index.expr_type(self.module), Type::I64);
+        self.push_instruction(Instruction::Dump(index));
+    }
+
     pub fn jump(&mut self, target: impl Into<TargetBlock>) {
         self.push_instruction(Instruction::Jump(target.into()));
     }
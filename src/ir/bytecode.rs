@@ -0,0 +1,325 @@
+use super::{
+    block::Block,
+    instruction::{BinaryOp, CastOp, Expr, Instruction, LeafExpr, TargetBlock, TestOp, UnaryOp},
+    register::RegisterID,
+    types::Type,
+    Module,
+};
+use std::{collections::HashMap, fmt::Display};
+
+pub type Reg = u32;
+pub type Label = u32;
+
+/// A flat, register based lowering of a [`Module`].
+///
+/// Every value lives in a `u64` register, normalized to its type's width, and
+/// constants are preloaded into registers of their own. Jump targets are op
+/// offsets and block arguments are passed by plain register moves.
+#[derive(Clone, Debug)]
+pub struct Bytecode {
+    pub ops: Vec<Op>,
+    pub registers: usize,
+    pub constants: Vec<(Reg, u64)>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Op {
+    /// Starts a block of this many IR instructions, which is what fuel is
+    /// charged for.
+    Enter(u32),
+    Load(Reg, Reg),
+    Store(Reg, Reg),
+    /// Fails if the first register is left of the tape and grows the tape up
+    /// to the second.
+    Grow(Reg, Reg),
+    Output(Reg),
+    Input(Reg, Reg),
+    Dump(Reg),
+    Move(Reg, Reg),
+    Binary(fn(u64, u64) -> u64, Reg, Reg, Reg),
+    Unary(fn(u64) -> u64, Reg, Reg),
+    Equal(Reg, Reg, Reg),
+    NotEqual(Reg, Reg, Reg),
+    Jump(Label),
+    Branch(Reg, Label, Label),
+    Halt,
+}
+
+/// An operation the VM has no meaning for, such as a signed division of
+/// booleans.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LowerError {
+    Binary(BinaryOp, Type),
+    Cast(CastOp, Type, Type),
+}
+impl Display for LowerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Binary(op, ty) => write!(f, "{op} is undefined on {ty}"),
+            Self::Cast(op, from, to) => write!(f, "cannot {op} {from} to {to}"),
+        }
+    }
+}
+
+/// Lowers `module`, failing on operations the VM cannot run.
+pub fn lower(module: &Module) -> Result<Bytecode, LowerError> {
+    let mut lowering = Lowering {
+        module,
+        ops: Vec::new(),
+        labels: vec![0; module.blocks.len()],
+        constants: HashMap::new(),
+        trampolines: Vec::new(),
+        scratch: module.registers.len() as Reg,
+    };
+
+    lowering.ops.push(Op::Jump(module.entry_block().0 as Label));
+    for block in &module.blocks {
+        lowering.lower_block(block)?;
+    }
+    while let Some((label, target)) = lowering.trampolines.pop() {
+        lowering.labels[label as usize] = lowering.ops.len();
+        lowering.jump(&target);
+    }
+    lowering.resolve_labels();
+
+    let mut constants: Vec<_> = lowering
+        .constants
+        .into_iter()
+        .map(|(v, r)| (r, v))
+        .collect();
+    constants.sort_unstable();
+    Ok(Bytecode {
+        ops: lowering.ops,
+        registers: module.registers.len() + 1 + constants.len(),
+        constants,
+    })
+}
+
+struct Lowering<'a> {
+    module: &'a Module,
+    ops: Vec<Op>,
+    labels: Vec<usize>,
+    constants: HashMap<u64, Reg>,
+    trampolines: Vec<(Label, TargetBlock)>,
+    scratch: Reg,
+}
+impl Lowering<'_> {
+    fn lower_block(&mut self, block: &Block) -> Result<(), LowerError> {
+        self.labels[block.id().0] = self.ops.len();
+        self.ops.push(Op::Enter(block.body().len() as u32));
+        for instruction in block.body() {
+            use Instruction::*;
+            let op = match instruction {
+                Nop => continue,
+                &LoadCell(target, ref index) => Op::Load(reg(target), self.leaf(index)),
+                StoreCell(index, value) => Op::Store(self.leaf(index), self.leaf(value)),
+                BoundsCheck(start, end) => Op::Grow(self.leaf(start), self.leaf(end)),
+                &Assign(target, ref expr) => self.assign(reg(target), expr)?,
+                Output(value) => Op::Output(self.leaf(value)),
+                &Input(target, ref default) => Op::Input(reg(target), self.leaf(default)),
+                Dump(index) => Op::Dump(self.leaf(index)),
+                Jump(target) => {
+                    self.jump(target);
+                    return Ok(());
+                }
+                Branch(c, then, els) => {
+                    let c = self.leaf(c);
+                    let (then, els) = (self.edge(then), self.edge(els));
+                    self.ops.push(Op::Branch(c, then, els));
+                    return Ok(());
+                }
+            };
+            self.ops.push(op);
+        }
+        self.ops.push(Op::Halt);
+        Ok(())
+    }
+
+    fn assign(&mut self, target: Reg, expr: &Expr) -> Result<Op, LowerError> {
+        Ok(match expr {
+            Expr::Leaf(a) => Op::Move(target, self.leaf(a)),
+            &Expr::Binary(ref a, op, ref b) => {
+                let f = binary_fn(op, a.expr_type(self.module))?;
+                Op::Binary(f, target, self.leaf(a), self.leaf(b))
+            }
+            &Expr::Unary(ref a, op) => {
+                let f = unary_fn(op, a.expr_type(self.module));
+                Op::Unary(f, target, self.leaf(a))
+            }
+            Expr::Test(a, TestOp::Equal, b) => Op::Equal(target, self.leaf(a), self.leaf(b)),
+            Expr::Test(a, TestOp::NotEqual, b) => Op::NotEqual(target, self.leaf(a), self.leaf(b)),
+            &Expr::Test(ref a, op, ref b) => {
+                let f = test_fn(op, a.expr_type(self.module));
+                Op::Binary(f, target, self.leaf(a), self.leaf(b))
+            }
+            &Expr::Cast(ref a, op, to) => {
+                let f = cast_fn(op, a.expr_type(self.module), to)?;
+                Op::Unary(f, target, self.leaf(a))
+            }
+        })
+    }
+
+    fn jump(&mut self, target: &TargetBlock) {
+        let params = self.module[target.id].parameters();
+        let moves = params
+            .iter()
+            .zip(&target.args)
+            .map(|(&param, arg)| (reg(param), self.leaf(arg)))
+            .collect();
+        self.parallel_move(moves);
+        self.ops.push(Op::Jump(target.id.0 as Label));
+    }
+    fn edge(&mut self, target: &TargetBlock) -> Label {
+        if target.args.is_empty() {
+            return target.id.0 as Label;
+        }
+        let label = self.labels.len() as Label;
+        self.labels.push(0);
+        self.trampolines.push((label, target.clone()));
+        label
+    }
+
+    fn parallel_move(&mut self, mut moves: Vec<(Reg, Reg)>) {
+        moves.retain(|&(dst, src)| dst != src);
+        while !moves.is_empty() {
+            let ready = moves
+                .iter()
+                .position(|&(dst, _)| moves.iter().all(|&(_, src)| src != dst));
+            match ready {
+                Some(i) => {
+                    let (dst, src) = moves.remove(i);
+                    self.ops.push(Op::Move(dst, src));
+                }
+                None => {
+                    let (_, src) = moves[0];
+                    self.ops.push(Op::Move(self.scratch, src));
+                    for (_, s) in &mut moves {
+                        if *s == src {
+                            *s = self.scratch;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn leaf(&mut self, expr: &LeafExpr) -> Reg {
+        match *expr {
+            LeafExpr::Register(r) => reg(r),
+            LeafExpr::Int(_) => {
+                let value = to_u64(expr.eval_const().unwrap());
+                let next = self.scratch + 1 + self.constants.len() as Reg;
+                *self.constants.entry(value).or_insert(next)
+            }
+        }
+    }
+
+    fn resolve_labels(&mut self) {
+        for op in &mut self.ops {
+            match op {
+                Op::Jump(target) => *target = self.labels[*target as usize] as Label,
+                Op::Branch(_, then, els) => {
+                    *then = self.labels[*then as usize] as Label;
+                    *els = self.labels[*els as usize] as Label;
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn reg(r: RegisterID) -> Reg {
+    r.0 as Reg
+}
+
+fn to_u64(value: super::exec::Value) -> u64 {
+    use super::exec::Value::*;
+    match value {
+        Uninit => panic!("uninitialized constant"),
+        I1(b) => b as u64,
+        I8(v) => v as u64,
+        I64(v) => v,
+    }
+}
+
+fn binary_fn(op: BinaryOp, ty: Type) -> Result<fn(u64, u64) -> u64, LowerError> {
+    use BinaryOp::*;
+    Ok(match (ty, op) {
+        (Type::I1, Add | Sub | Xor) => |a, b| a ^ b,
+        (Type::I1, Mul | And) => |a, b| a & b,
+        (Type::I1, Or) => |a, b| a | b,
+        (Type::I1, UDiv) => |a, _| a,
+        (Type::I1, UMod) => |_, _| 0,
+        (Type::I1, IDiv | IMod) => return Err(LowerError::Binary(op, ty)),
+        (Type::I1, Shl | LShr) => |a, b| a & !b & 1,
+        (Type::I1, AShr) => |a, _| a,
+
+        (Type::I8, Add) => |a, b| (a as u8).wrapping_add(b as u8) as u64,
+        (Type::I8, Sub) => |a, b| (a as u8).wrapping_sub(b as u8) as u64,
+        (Type::I8, Mul) => |a, b| (a as u8).wrapping_mul(b as u8) as u64,
+        (Type::I8, UDiv) => |a, b| (a as u8 / b as u8) as u64,
+        (Type::I8, UMod) => |a, b| (a as u8 % b as u8) as u64,
+        (Type::I8, IDiv) => |a, b| (a as i8).wrapping_div(b as i8) as u8 as u64,
+        (Type::I8, IMod) => |a, b| (a as i8).wrapping_rem(b as i8) as u8 as u64,
+        (Type::I8, Shl) => |a, b| (a as u8).checked_shl(b as u32).unwrap_or(0) as u64,
+        (Type::I8, LShr) => |a, b| (a as u8).checked_shr(b as u32).unwrap_or(0) as u64,
+        (Type::I8, AShr) => |a, b| ((a as i8) >> b.min(7)) as u8 as u64,
+
+        (Type::I64, Add) => |a, b| a.wrapping_add(b),
+        (Type::I64, Sub) => |a, b| a.wrapping_sub(b),
+        (Type::I64, Mul) => |a, b| a.wrapping_mul(b),
+        (Type::I64, UDiv) => |a, b| a / b,
+        (Type::I64, UMod) => |a, b| a % b,
+        (Type::I64, IDiv) => |a, b| (a as i64).wrapping_div(b as i64) as u64,
+        (Type::I64, IMod) => |a, b| (a as i64).wrapping_rem(b as i64) as u64,
+        (Type::I64, Shl) => |a, b| if b < 64 { a << b } else { 0 },
+        (Type::I64, LShr) => |a, b| if b < 64 { a >> b } else { 0 },
+        (Type::I64, AShr) => |a, b| ((a as i64) >> b.min(63)) as u64,
+
+        (Type::I8 | Type::I64, And) => |a, b| a & b,
+        (Type::I8 | Type::I64, Or) => |a, b| a | b,
+        (Type::I8 | Type::I64, Xor) => |a, b| a ^ b,
+    })
+}
+
+fn unary_fn(op: UnaryOp, ty: Type) -> fn(u64) -> u64 {
+    use UnaryOp::*;
+    match (ty, op) {
+        (Type::I1, Not) => |a| a ^ 1,
+        (Type::I1, Neg) => |a| a,
+        (Type::I8, Not) => |a| !(a as u8) as u64,
+        (Type::I8, Neg) => |a| (a as u8).wrapping_neg() as u64,
+        (Type::I64, Not) => |a| !a,
+        (Type::I64, Neg) => |a| a.wrapping_neg(),
+    }
+}
+
+fn test_fn(op: TestOp, ty: Type) -> fn(u64, u64) -> u64 {
+    use TestOp::*;
+    match (ty, op) {
+        (_, Equal) => |a, b| (a == b) as u64,
+        (_, NotEqual) => |a, b| (a != b) as u64,
+        (_, ULessThan) => |a, b| (a < b) as u64,
+        (_, ULessEqual) => |a, b| (a <= b) as u64,
+        (Type::I1, ILessThan) => |a, b| (a > b) as u64,
+        (Type::I1, ILessEqual) => |a, b| (a >= b) as u64,
+        (Type::I8, ILessThan) => |a, b| ((a as i8) < (b as i8)) as u64,
+        (Type::I8, ILessEqual) => |a, b| ((a as i8) <= (b as i8)) as u64,
+        (Type::I64, ILessThan) => |a, b| ((a as i64) < (b as i64)) as u64,
+        (Type::I64, ILessEqual) => |a, b| ((a as i64) <= (b as i64)) as u64,
+    }
+}
+
+fn cast_fn(op: CastOp, from: Type, to: Type) -> Result<fn(u64) -> u64, LowerError> {
+    use CastOp::*;
+    Ok(match (op, from, to) {
+        _ if from == to => |a| a,
+        (ZExt, _, _) if from.bits() < to.bits() => |a| a,
+        (SExt, Type::I1, Type::I8) => |a| a.wrapping_neg() as u8 as u64,
+        (SExt, Type::I1, Type::I64) => |a| a.wrapping_neg(),
+        (SExt, Type::I8, Type::I64) => |a| a as u8 as i8 as i64 as u64,
+        (Trunc, _, Type::I8) if from.bits() > 8 => |a| a as u8 as u64,
+        (Trunc, _, Type::I1) => |a| a & 1,
+        _ => return Err(LowerError::Cast(op, from, to)),
+    })
+}
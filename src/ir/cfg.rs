@@ -0,0 +1,178 @@
+use super::{block::BlockID, instruction::Instruction, Module};
+
+/// The control-flow graph of a module, with its dominator tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cfg {
+    entry: BlockID,
+    successors: Vec<Vec<BlockID>>,
+    predecessors: Vec<Vec<BlockID>>,
+    /// The immediate dominator of every reachable block; the entry is its own.
+    idom: Vec<Option<BlockID>>,
+}
+impl Cfg {
+    pub fn new(module: &Module) -> Self {
+        let entry = module.entry_block();
+        let successors: Vec<Vec<BlockID>> = module
+            .blocks
+            .iter()
+            .map(|b| match b.body.last() {
+                Some(Instruction::Jump(target)) => vec![target.id],
+                Some(Instruction::Branch(_, then, els)) => vec![then.id, els.id],
+                _ => Vec::new(),
+            })
+            .collect();
+        let mut predecessors = vec![Vec::new(); successors.len()];
+        for (from, targets) in successors.iter().enumerate() {
+            for target in targets {
+                predecessors[target.0].push(BlockID(from));
+            }
+        }
+
+        let mut cfg = Self {
+            entry,
+            successors,
+            predecessors,
+            idom: Vec::new(),
+        };
+        cfg.idom = cfg.compute_dominators();
+        cfg
+    }
+
+    pub fn entry(&self) -> BlockID {
+        self.entry
+    }
+    pub fn successors(&self, block: BlockID) -> &[BlockID] {
+        &self.successors[block.0]
+    }
+    pub fn predecessors(&self, block: BlockID) -> &[BlockID] {
+        &self.predecessors[block.0]
+    }
+    pub fn is_reachable(&self, block: BlockID) -> bool {
+        self.idom[block.0].is_some()
+    }
+
+    /// The closest strict dominator of `block`, or `None` for the entry and
+    /// unreachable blocks.
+    pub fn immediate_dominator(&self, block: BlockID) -> Option<BlockID> {
+        self.idom[block.0].filter(|&d| d != block)
+    }
+    pub fn dominates(&self, a: BlockID, mut b: BlockID) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.immediate_dominator(b) {
+                Some(d) => b = d,
+                None => return false,
+            }
+        }
+    }
+
+    /// Edges whose target dominates their source.
+    pub fn back_edges(&self) -> impl Iterator<Item = (BlockID, BlockID)> + '_ {
+        (0..self.successors.len())
+            .map(BlockID)
+            .flat_map(move |from| self.successors(from).iter().map(move |&to| (from, to)))
+            .filter(|&(from, to)| self.dominates(to, from))
+    }
+
+    /// The natural loops of the graph as `(header, blocks)`, with loops sharing a
+    /// header merged, ordered by header.
+    pub fn loops(&self) -> Vec<(BlockID, Vec<BlockID>)> {
+        let mut loops: Vec<(BlockID, Vec<bool>)> = Vec::new();
+        for (latch, header) in self.back_edges() {
+            let index = match loops.iter().position(|(h, _)| *h == header) {
+                Some(index) => index,
+                None => {
+                    let mut members = vec![false; self.successors.len()];
+                    members[header.0] = true;
+                    loops.push((header, members));
+                    loops.len() - 1
+                }
+            };
+            let members = &mut loops[index].1;
+            let mut stack = vec![latch];
+            while let Some(block) = stack.pop() {
+                if !members[block.0] {
+                    members[block.0] = true;
+                    stack.extend(self.predecessors(block).iter().copied());
+                }
+            }
+        }
+
+        loops.sort_by_key(|(header, _)| header.0);
+        loops
+            .into_iter()
+            .map(|(header, members)| {
+                let blocks = (0..members.len()).filter(|&b| members[b]).map(BlockID);
+                (header, blocks.collect())
+            })
+            .collect()
+    }
+
+    fn reverse_postorder(&self) -> Vec<BlockID> {
+        let mut visited = vec![false; self.successors.len()];
+        let mut order = Vec::new();
+        let mut stack = vec![(self.entry, 0)];
+        visited[self.entry.0] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.successors(block).get(next) {
+                Some(&successor) => {
+                    stack.push((block, next + 1));
+                    if !visited[successor.0] {
+                        visited[successor.0] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    // Cooper, Harvey and Kennedy's "A Simple, Fast Dominance Algorithm".
+    fn compute_dominators(&self) -> Vec<Option<BlockID>> {
+        let order = self.reverse_postorder();
+        let mut rank = vec![usize::MAX; self.successors.len()];
+        for (i, block) in order.iter().enumerate() {
+            rank[block.0] = i;
+        }
+
+        let mut idom = vec![None; self.successors.len()];
+        idom[self.entry.0] = Some(self.entry);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let mut processed = self
+                    .predecessors(block)
+                    .iter()
+                    .filter(|p| idom[p.0].is_some());
+                let Some(&first) = processed.next() else {
+                    continue;
+                };
+                let new = processed.fold(first, |mut a, &b| {
+                    let mut b = b;
+                    while a != b {
+                        while rank[a.0] > rank[b.0] {
+                            a = idom[a.0].unwrap();
+                        }
+                        while rank[b.0] > rank[a.0] {
+                            b = idom[b.0].unwrap();
+                        }
+                    }
+                    a
+                });
+                if idom[block.0] != Some(new) {
+                    idom[block.0] = Some(new);
+                    changed = true;
+                }
+            }
+        }
+        idom
+    }
+}
@@ -0,0 +1,138 @@
+use super::{
+    block::BlockID,
+    cfg::Cfg,
+    instruction::{Instruction, TargetBlock},
+    printing::Printer,
+    Module,
+};
+use std::io::{self, Write};
+
+/// What [`DotWriter`] highlights on top of the control-flow graph.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Fill the blocks of natural loops, bold their headers and dash back edges.
+    pub loops: bool,
+    /// Add the dominator tree as dotted edges.
+    pub dominators: bool,
+}
+
+const LOOP_COLORS: &[&str] = &["#dbe9f6", "#fde2c8", "#d8f0d2", "#f3d6e8", "#ece3c4"];
+
+/// Renders a module's control-flow graph in Graphviz DOT.
+pub struct DotWriter<O> {
+    out: O,
+    options: DotOptions,
+}
+impl<O: Write> DotWriter<O> {
+    pub fn new(out: O, options: DotOptions) -> Self {
+        Self { out, options }
+    }
+
+    pub fn write_module(&mut self, m: &Module) -> io::Result<()> {
+        let cfg = Cfg::new(m);
+        let loops = if self.options.loops {
+            cfg.loops()
+        } else {
+            Vec::new()
+        };
+        // Inner loops are smaller than the loops containing them, so colouring
+        // the largest first leaves every block with its innermost loop.
+        let mut innermost = vec![None; m.blocks.len()];
+        let mut by_size: Vec<_> = loops.iter().enumerate().collect();
+        by_size.sort_by_key(|(_, (_, blocks))| std::cmp::Reverse(blocks.len()));
+        for (i, (_, blocks)) in by_size {
+            for block in blocks {
+                innermost[block.0] = Some(i);
+            }
+        }
+
+        writeln!(self.out, "digraph module {{")?;
+        writeln!(self.out, "\tnode [shape=box, fontname=monospace];")?;
+        writeln!(self.out, "\tedge [fontname=monospace];")?;
+        for block in &m.blocks {
+            let id = block.id();
+            let mut text = Vec::new();
+            Printer::new(&mut text).print_block(block, m)?;
+            write!(self.out, "\t{} [label=\"", id.0)?;
+            self.write_label(&String::from_utf8_lossy(&text))?;
+            write!(self.out, "\"")?;
+            if id == cfg.entry() {
+                write!(self.out, ", peripheries=2")?;
+            }
+            if let Some(i) = innermost[id.0] {
+                let color = LOOP_COLORS[i % LOOP_COLORS.len()];
+                write!(self.out, ", style=filled, fillcolor=\"{color}\"")?;
+            }
+            if loops.iter().any(|(header, _)| *header == id) {
+                write!(self.out, ", penwidth=2")?;
+            }
+            writeln!(self.out, "];")?;
+        }
+
+        for block in &m.blocks {
+            let from = block.id();
+            match block.body.last() {
+                Some(Instruction::Jump(target)) => self.write_edge(&cfg, from, None, target)?,
+                Some(Instruction::Branch(_, then, els)) => {
+                    self.write_edge(&cfg, from, Some(true), then)?;
+                    self.write_edge(&cfg, from, Some(false), els)?;
+                }
+                _ => (),
+            }
+        }
+
+        if self.options.dominators {
+            for block in &m.blocks {
+                if let Some(dominator) = cfg.immediate_dominator(block.id()) {
+                    writeln!(
+                        self.out,
+                        "\t{} -> {} [style=dotted, color=gray, arrowhead=empty, constraint=false];",
+                        dominator.0,
+                        block.id().0
+                    )?;
+                }
+            }
+        }
+        writeln!(self.out, "}}")
+    }
+
+    fn write_edge(
+        &mut self,
+        cfg: &Cfg,
+        from: BlockID,
+        polarity: Option<bool>,
+        target: &TargetBlock,
+    ) -> io::Result<()> {
+        let mut label = polarity.map(|p| p.to_string()).unwrap_or_default();
+        if !target.args.is_empty() {
+            let args: Vec<_> = target.args.iter().map(ToString::to_string).collect();
+            if !label.is_empty() {
+                label.push(' ');
+            }
+            label += &format!("({})", args.join(", "));
+        }
+
+        write!(self.out, "\t{} -> {} [label=\"", from.0, target.id.0)?;
+        self.write_label(&label)?;
+        write!(self.out, "\"")?;
+        if polarity == Some(false) {
+            write!(self.out, ", color=red")?;
+        }
+        if self.options.loops && cfg.dominates(target.id, from) {
+            write!(self.out, ", style=dashed")?;
+        }
+        writeln!(self.out, "];")
+    }
+
+    fn write_label(&mut self, text: &str) -> io::Result<()> {
+        for c in text.chars() {
+            match c {
+                '"' | '\\' => write!(self.out, "\\{c}")?,
+                '\n' => write!(self.out, "\\l")?,
+                '\t' => write!(self.out, "  ")?,
+                c => write!(self.out, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
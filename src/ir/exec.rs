@@ -1,94 +1,437 @@
 use super::{
-    block::{Block, BlockID},
-    instruction::{BinaryOp, Expr, LeafExpr, TargetBlock, TestOp, UnaryOp},
+    block::BlockID,
+    instruction::{BinaryOp, CastOp, Expr, LeafExpr, TargetBlock, TestOp, UnaryOp},
+    limits::{ExecError, Limits, Meter},
+    profile::Profile,
     register::RegisterID,
+    snapshot::Snapshot,
+    trace::{self, TraceWriter},
+    types::Type,
     Module,
 };
 use crate::ir::instruction::Instruction;
 use std::{
-    io::{self, Read, Write},
+    cmp::Ordering,
+    io::{self, stderr, Read, Write},
     iter::once,
+    mem,
     ops::{Index, IndexMut},
 };
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-pub struct Exec<O, I> {
+/// How many instructions the async path runs before yielding to the runtime.
+#[cfg(feature = "async")]
+pub const YIELD_INTERVAL: u64 = 1 << 16;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tape {
     cells: Vec<u8>,
-    registers: Vec<Value>,
+    max_len: Option<usize>,
+}
+impl Tape {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn replace_cells(&mut self, cells: Vec<u8>) {
+        self.cells = cells;
+    }
+
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+    }
+    /// Returns false, leaving the tape as is, if `length` exceeds the limit.
+    pub fn grow_to(&mut self, length: usize) -> bool {
+        if self.max_len.is_some_and(|max| length > max) {
+            return false;
+        }
+        if length > self.cells.len() {
+            self.cells.resize(length, 0);
+        }
+        true
+    }
+}
+impl Index<usize> for Tape {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.cells[index]
+    }
+}
+impl IndexMut<usize> for Tape {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.cells[index]
+    }
+}
+
+/// How many cells either side of the pointer a dump shows.
+pub const DUMP_RADIUS: usize = 8;
+
+/// Writes the cells around `pointer` on one line, marking the current one.
+pub fn dump_tape(mut out: impl Write, cells: &[u8], pointer: isize) -> io::Result<()> {
+    let start = (pointer.max(0) as usize).saturating_sub(DUMP_RADIUS);
+    let end = (pointer + DUMP_RADIUS as isize + 1).max(0) as usize;
+    write!(out, "cells {start}..{end}, pointer {pointer}:")?;
+    for i in start..end {
+        let cell = cells.get(i).copied().unwrap_or(0);
+        if i as isize == pointer {
+            write!(out, " [{cell}]")?;
+        } else {
+            write!(out, " {cell}")?;
+        }
+    }
+    writeln!(out)
+}
+
+pub struct Io<O, I> {
     stdout: O,
     stdin: I,
 }
+impl<O, I> Io<O, I> {
+    pub fn new(stdout: O, stdin: I) -> Self {
+        Self { stdout, stdin }
+    }
+
+    pub fn stdout(&self) -> &O {
+        &self.stdout
+    }
+}
+impl<O: Write, I: Read> Io<O, I> {
+    pub fn write(&mut self, byte: u8) -> io::Result<()> {
+        self.stdout.write_all(&[byte])?;
+        self.stdout.flush()
+    }
+    /// Reads one byte, or returns `default` at end of input.
+    ///
+    /// An input that isn't ready yet fails with [`io::ErrorKind::WouldBlock`].
+    pub fn read(&mut self, default: u8) -> io::Result<u8> {
+        let mut buffer = [0];
+        let read = self.stdin.read(&mut buffer)?;
+        Ok(if read == 0 { default } else { buffer[0] })
+    }
+}
+#[cfg(feature = "async")]
+impl<O: AsyncWrite + Unpin, I: AsyncRead + Unpin> Io<O, I> {
+    pub async fn write_async(&mut self, byte: u8) -> io::Result<()> {
+        self.stdout.write_all(&[byte]).await?;
+        self.stdout.flush().await
+    }
+    pub async fn read_async(&mut self, default: u8) -> io::Result<u8> {
+        let mut buffer = [0];
+        let read = self.stdin.read(&mut buffer).await?;
+        Ok(if read == 0 { default } else { buffer[0] })
+    }
+}
+
+pub struct Exec<O, I> {
+    tape: Tape,
+    registers: Vec<Value>,
+    io: Io<O, I>,
+    meter: Meter,
+    position: Position,
+    profile: Option<Profile>,
+    trace: Option<TraceWriter>,
+}
 impl<O: Write, I: Read> Exec<O, I> {
+    pub fn exec_program(&mut self, module: &Module) -> Result<(), ExecError> {
+        self.start(module);
+        match self.run(module, None)? {
+            Status::Halted => Ok(()),
+            _ => Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
+        }
+    }
+
+    /// Continues from the current position for at most `steps` instructions.
+    ///
+    /// Stops early with [`Status::NeedsInput`] when reading would block; the
+    /// input instruction is retried on the next call.
+    pub fn run(&mut self, module: &Module, steps: Option<u64>) -> Result<Status, ExecError> {
+        let mut budget = steps.unwrap_or(u64::MAX);
+        loop {
+            match self.advance(module, &mut budget)? {
+                Event::Halted => return Ok(Status::Halted),
+                Event::OutOfSteps => return Ok(Status::OutOfSteps),
+                Event::Output(byte) => self.io.write(byte)?,
+                Event::Input(target, default) => match self.io.read(default) {
+                    Ok(value) => self.finish_input(target, value)?,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Status::NeedsInput)
+                    }
+                    Err(err) => return Err(err.into()),
+                },
+            }
+        }
+    }
+}
+#[cfg(feature = "async")]
+impl<O: AsyncWrite + Unpin, I: AsyncRead + Unpin> Exec<O, I> {
+    /// Like [`Exec::exec_program`], but awaits every input and output and
+    /// yields to the runtime every [`YIELD_INTERVAL`] instructions.
+    pub async fn exec_program_async(&mut self, module: &Module) -> Result<(), ExecError> {
+        self.start(module);
+        let mut budget = YIELD_INTERVAL;
+        loop {
+            match self.advance(module, &mut budget)? {
+                Event::Halted => return Ok(()),
+                Event::OutOfSteps => {
+                    tokio::task::yield_now().await;
+                    budget = YIELD_INTERVAL;
+                }
+                Event::Output(byte) => self.io.write_async(byte).await?,
+                Event::Input(target, default) => {
+                    let value = self.io.read_async(default).await?;
+                    self.finish_input(target, value)?;
+                }
+            }
+        }
+    }
+}
+impl<O, I> Exec<O, I> {
     pub fn new(stdout: O, stdin: I) -> Self {
         Self {
-            cells: Vec::new(),
+            tape: Tape::new(),
             registers: Vec::new(),
-            stdout,
-            stdin,
+            io: Io::new(stdout, stdin),
+            meter: Meter::default(),
+            position: Position::Halted,
+            profile: None,
+            trace: None,
         }
     }
 
-    pub fn exec_program(&mut self, module: &Module) -> io::Result<()> {
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.tape.set_max_len(limits.max_tape);
+        self.meter = Meter::new(limits);
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        self.tape.cells()
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    /// Counts block and instruction executions from the next [`Exec::start`] on.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Records every block entered, register and cell written, and byte read
+    /// or written to `out`, see [`trace`].
+    pub fn record_trace(&mut self, out: impl Write + Send + 'static) -> io::Result<()> {
+        self.trace = Some(TraceWriter::new(out)?);
+        Ok(())
+    }
+
+    /// Resets the registers and points execution at the entry block.
+    pub fn start(&mut self, module: &Module) {
+        self.meter.reset();
         let registers = module.registers.len();
         self.registers.clear();
         self.registers
             .extend(once(Value::Uninit).cycle().take(registers));
+        self.position = Position::Jump(module.entry_block(), Vec::new());
+        if let Some(profile) = &mut self.profile {
+            *profile = Profile::new(module);
+        }
+    }
 
-        let entry = module.entry_block();
-        let mut action = Action::Jump(entry, Vec::new());
-
-        loop {
-            match action {
-                Action::Halt => break,
-                Action::Jump(block, args) => action = self.exec_block(&module[block], args)?,
+    /// Runs until the next input or output, the end of the program or the end
+    /// of `budget`. An input instruction stays current until it is finished.
+    fn advance(&mut self, module: &Module, budget: &mut u64) -> Result<Event, ExecError> {
+        'run: loop {
+            match mem::replace(&mut self.position, Position::Halted) {
+                Position::Halted => {
+                    if let Some(trace) = &mut self.trace {
+                        trace.flush()?;
+                    }
+                    return Ok(Event::Halted);
+                }
+                Position::Jump(block, args) => {
+                    let block = &module[block];
+                    self.meter
+                        .tick(block.body().len() as u64, self.tape.len())?;
+                    for (&param, arg) in block.parameters().iter().zip(args) {
+                        self[param] = arg;
+                    }
+                    if let Some(profile) = &mut self.profile {
+                        profile.blocks[block.id().0] += 1;
+                    }
+                    if let Some(trace) = &mut self.trace {
+                        trace.record(trace::Event::Enter(block.id()))?;
+                        for &param in block.parameters() {
+                            trace.record(trace::Event::Register(param, self.registers[param.0]))?;
+                        }
+                    }
+                    self.position = Position::At(block.id(), 0);
+                }
+                Position::At(block, start) => {
+                    let body = &module[block].body()[start..];
+                    for (i, instruction) in (start..).zip(body) {
+                        if *budget == 0 {
+                            self.position = Position::At(block, i);
+                            return Ok(Event::OutOfSteps);
+                        }
+                        *budget -= 1;
+                        let step = self.step(instruction)?;
+                        if let Some(profile) = &mut self.profile {
+                            if !matches!(step, Step::Input(..)) {
+                                profile.instructions[block.0][i] += 1;
+                            }
+                            if let Step::Output(_) = step {
+                                profile.output_bytes += 1;
+                            }
+                        }
+                        if self.trace.is_some() {
+                            self.record(instruction, &step)?;
+                        }
+                        match step {
+                            Step::Next => (),
+                            Step::Jump(next) => {
+                                self.position = next;
+                                continue 'run;
+                            }
+                            Step::Output(byte) => {
+                                self.position = Position::At(block, i + 1);
+                                return Ok(Event::Output(byte));
+                            }
+                            Step::Input(target, default) => {
+                                self.position = Position::At(block, i);
+                                return Ok(Event::Input(target, default));
+                            }
+                        }
+                    }
+                }
             }
         }
-
+    }
+    fn finish_input(&mut self, target: RegisterID, value: u8) -> io::Result<()> {
+        self[target] = Value::I8(value);
+        if let Some(trace) = &mut self.trace {
+            trace.record(trace::Event::Input(value))?;
+            trace.record(trace::Event::Register(target, Value::I8(value)))?;
+        }
+        if let Position::At(block, i) = &mut self.position {
+            if let Some(profile) = &mut self.profile {
+                profile.instructions[block.0][*i] += 1;
+            }
+            *i += 1;
+        }
         Ok(())
     }
 
-    fn exec_block(&mut self, block: &Block, args: Vec<Value>) -> io::Result<Action> {
-        for (&param, arg) in block.parameters().into_iter().zip(args) {
-            self[param] = arg;
-        }
-        let _id = block.id();
-        for (_i, instruction) in block.body().into_iter().enumerate() {
-            use Instruction::*;
-            match instruction {
-                &Nop => (),
-                &LoadCell(target, ref index) => self.load_cell(target, index),
-                StoreCell(index, value) => self.store_cell(index, value),
-                BoundsCheck(start, end) => self.bounds_check(start, end),
-                &Assign(target, ref expr) => self.assign(target, expr),
-                &Output(ref value) => self.output(value)?,
-                &Input(target, ref default) => self.input(target, default)?,
-                Jump(target) => return Ok(self.jump(target)),
-                Branch(c, then, els) => return Ok(self.branch(c, then, els)),
+    fn record(&mut self, instruction: &Instruction, step: &Step) -> io::Result<()> {
+        let event = match (instruction, step) {
+            (&Instruction::LoadCell(target, _) | &Instruction::Assign(target, _), _) => {
+                trace::Event::Register(target, self[target])
+            }
+            (Instruction::StoreCell(index, _), _) => {
+                let Value::I64(cell) = self.eval_leaf_expr(index) else {
+                    panic!("{index} is not of type i64")
+                };
+                let value = self.tape[cell as usize];
+                trace::Event::Store { cell, value }
+            }
+            (_, &Step::Output(byte)) => trace::Event::Output(byte),
+            _ => return Ok(()),
+        };
+        self.trace.as_mut().unwrap().record(event)
+    }
+
+    fn step(&mut self, instruction: &Instruction) -> Result<Step, ExecError> {
+        use Instruction::*;
+        match instruction {
+            &Nop => (),
+            &LoadCell(target, ref index) => self.load_cell(target, index),
+            StoreCell(index, value) => self.store_cell(index, value),
+            BoundsCheck(start, end) => self.bounds_check(start, end)?,
+            &Assign(target, ref expr) => self.assign(target, expr),
+            Output(value) => return Ok(Step::Output(self.eval_i8(value))),
+            &Input(target, ref default) => return Ok(Step::Input(target, self.eval_i8(default))),
+            Dump(index) => {
+                let Value::I64(index) = self.eval_leaf_expr(index) else {
+                    panic!("{index} is not of type i64")
+                };
+                dump_tape(stderr().lock(), self.tape.cells(), index as isize)?;
             }
+            Jump(target) => return Ok(Step::Jump(self.jump(target))),
+            Branch(c, then, els) => return Ok(Step::Jump(self.branch(c, then, els))),
         }
+        Ok(Step::Next)
+    }
 
-        Ok(Action::Halt)
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            position: self.position.clone(),
+            registers: self.registers.clone(),
+            cells: self.tape.cells().to_vec(),
+            progress: self.meter.progress(self.tape.len()),
+        }
+    }
+    /// Continues from `snapshot`, which must have been taken while running
+    /// `module`.
+    pub fn restore(&mut self, module: &Module, snapshot: Snapshot) -> io::Result<()> {
+        snapshot.validate(module)?;
+        self.position = snapshot.position;
+        self.registers = snapshot.registers;
+        self.tape.replace_cells(snapshot.cells);
+        self.meter.resume(snapshot.progress);
+        Ok(())
     }
 
     fn load_cell(&mut self, target: RegisterID, index: &LeafExpr) {
-        let Value::I64(index) = self.eval_leaf_expr(index) else { panic!("{index} is not of type i64") };
-        let cell = self.cells[index as usize];
+        let Value::I64(index) = self.eval_leaf_expr(index) else {
+            panic!("{index} is not of type i64")
+        };
+        let cell = self.tape[index as usize];
         self[target] = Value::I8(cell);
+        self.touch(index);
     }
     fn store_cell(&mut self, index: &LeafExpr, value: &LeafExpr) {
-        let Value::I64(index) = self.eval_leaf_expr(index) else { panic!() };
-        let Value::I8(value) = self.eval_leaf_expr(value) else { panic!() };
-        self.cells[index as usize] = value;
+        let Value::I64(index) = self.eval_leaf_expr(index) else {
+            panic!()
+        };
+        let Value::I8(value) = self.eval_leaf_expr(value) else {
+            panic!()
+        };
+        self.tape[index as usize] = value;
+        self.touch(index);
+    }
+    /// Bounds checks may reserve more of the tape than the program ever uses,
+    /// so the profile counts the cells actually loaded or stored.
+    fn touch(&mut self, index: u64) {
+        if let Some(profile) = &mut self.profile {
+            profile.tape_high_water = profile.tape_high_water.max(index as usize + 1);
+        }
     }
-    fn bounds_check(&mut self, _start: &LeafExpr, end: &LeafExpr) {
-        let Value::I64(end) = self.eval_leaf_expr(end) else { panic!() };
-        let needs_length = end as usize;
-        let has_length = self.cells.len();
-        if needs_length > has_length {
-            let difference = needs_length - has_length;
-            //eprintln!("Padding the cell vector by {} to length {}", difference, needs_length);
-            self.cells.extend(once(0).cycle().take(difference));
-            //eprintln!("Cells now have length {}", self.cells.len());
+    fn bounds_check(&mut self, start: &LeafExpr, end: &LeafExpr) -> Result<(), ExecError> {
+        let (Value::I64(start), Value::I64(end)) =
+            (self.eval_leaf_expr(start), self.eval_leaf_expr(end))
+        else {
+            panic!()
+        };
+        if (start as i64) < 0 {
+            return Err(self.meter.left_of_tape(self.tape.len()));
         }
+        if !self.tape.grow_to(end as usize) {
+            return Err(self.meter.tape_limit(self.tape.len()));
+        }
+        Ok(())
     }
 
     fn assign(&mut self, target: RegisterID, expr: &Expr) {
@@ -101,6 +444,7 @@ impl<O: Write, I: Read> Exec<O, I> {
             &Expr::Binary(ref a, op, ref b) => self.binary_op(op, a, b),
             &Expr::Unary(ref a, op) => self.unary_op(op, a),
             &Expr::Test(ref a, op, ref b) => self.test_op(op, a, b),
+            &Expr::Cast(ref a, op, ty) => Value::do_cast_op(self.eval_leaf_expr(a), op, ty),
         }
     }
 
@@ -123,28 +467,22 @@ impl<O: Write, I: Read> Exec<O, I> {
         Value::do_test_op(a, b, op)
     }
 
-    fn output(&mut self, value: &LeafExpr) -> io::Result<()> {
-        let Value::I8(value) = self.eval_leaf_expr(value) else { panic!() };
-        self.stdout.write(&[value])?;
-        self.stdout.flush()?;
-        Ok(())
-    }
-    fn input(&mut self, target: RegisterID, default: &LeafExpr) -> io::Result<()> {
-        let Value::I8(default) = self.eval_leaf_expr(default) else { panic!() };
-        let mut buffer = [0];
-        let read = self.stdin.read(&mut buffer)?;
-        let result = if read == 0 { default } else { buffer[0] };
-        self[target] = Value::I8(result);
-        Ok(())
+    fn eval_i8(&self, value: &LeafExpr) -> u8 {
+        let Value::I8(value) = self.eval_leaf_expr(value) else {
+            panic!()
+        };
+        value
     }
 
-    fn jump(&mut self, target: &TargetBlock) -> Action {
+    fn jump(&mut self, target: &TargetBlock) -> Position {
         let id = target.id;
         let args = target.args.iter().map(|a| self.eval_leaf_expr(a)).collect();
-        Action::Jump(id, args)
+        Position::Jump(id, args)
     }
-    fn branch(&mut self, c: &LeafExpr, then: &TargetBlock, els: &TargetBlock) -> Action {
-        let Value::I1(c) = self.eval_leaf_expr(c) else { panic!() };
+    fn branch(&mut self, c: &LeafExpr, then: &TargetBlock, els: &TargetBlock) -> Position {
+        let Value::I1(c) = self.eval_leaf_expr(c) else {
+            panic!()
+        };
         if c {
             self.jump(then)
         } else {
@@ -153,9 +491,9 @@ impl<O: Write, I: Read> Exec<O, I> {
     }
 
     fn eval_leaf_expr(&self, expr: &LeafExpr) -> Value {
-        match expr {
-            &LeafExpr::Register(r) => self[r],
-            &LeafExpr::Int(_) => expr.eval_const().unwrap(),
+        match *expr {
+            LeafExpr::Register(r) => self[r],
+            LeafExpr::Int(_) => expr.eval_const().unwrap(),
         }
     }
 }
@@ -172,12 +510,38 @@ impl<O, I> IndexMut<RegisterID> for Exec<O, I> {
     }
 }
 
-enum Action {
-    Halt,
+/// Where execution continues.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Position {
+    Halted,
+    /// About to enter a block with the given arguments.
     Jump(BlockID, Vec<Value>),
+    /// At an instruction inside a block.
+    At(BlockID, usize),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    OutOfSteps,
+    NeedsInput,
+}
+
+enum Step {
+    Next,
+    Jump(Position),
+    Output(u8),
+    Input(RegisterID, u8),
+}
+
+enum Event {
+    Halted,
+    OutOfSteps,
+    Output(u8),
+    Input(RegisterID, u8),
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Value {
     Uninit,
     I1(bool),
@@ -199,6 +563,10 @@ impl Value {
             And => Self::and(a, b),
             Or => Self::or(a, b),
             Xor => Self::xor(a, b),
+
+            Shl => Self::shl(a, b),
+            LShr => Self::lshr(a, b),
+            AShr => Self::ashr(a, b),
         }
     }
     fn add(a: Value, b: Value) -> Value {
@@ -311,6 +679,33 @@ impl Value {
             _ => panic!(),
         }
     }
+    fn shl(a: Value, b: Value) -> Value {
+        use Value::*;
+        match (a, b) {
+            (I1(a), I1(b)) => I1(a && !b),
+            (I8(a), I8(b)) => I8(a.checked_shl(b as u32).unwrap_or(0)),
+            (I64(a), I64(b)) => I64(if b < 64 { a << b } else { 0 }),
+            _ => panic!(),
+        }
+    }
+    fn lshr(a: Value, b: Value) -> Value {
+        use Value::*;
+        match (a, b) {
+            (I1(a), I1(b)) => I1(a && !b),
+            (I8(a), I8(b)) => I8(a.checked_shr(b as u32).unwrap_or(0)),
+            (I64(a), I64(b)) => I64(if b < 64 { a >> b } else { 0 }),
+            _ => panic!(),
+        }
+    }
+    fn ashr(a: Value, b: Value) -> Value {
+        use Value::*;
+        match (a, b) {
+            (I1(a), I1(_)) => I1(a),
+            (I8(a), I8(b)) => I8(((a as i8) >> b.min(7)) as u8),
+            (I64(a), I64(b)) => I64(((a as i64) >> b.min(63)) as u64),
+            _ => panic!(),
+        }
+    }
 
     pub fn do_unary_op(a: Value, op: UnaryOp) -> Value {
         use UnaryOp::*;
@@ -343,6 +738,10 @@ impl Value {
         match op {
             Equal => Self::test_equal(a, b),
             NotEqual => Self::test_not_equal(a, b),
+            ULessThan => Self::test_unsigned(a, b, |o| o.is_lt()),
+            ULessEqual => Self::test_unsigned(a, b, |o| o.is_le()),
+            ILessThan => Self::test_signed(a, b, |o| o.is_lt()),
+            ILessEqual => Self::test_signed(a, b, |o| o.is_le()),
         }
     }
     fn test_equal(a: Value, b: Value) -> Value {
@@ -363,6 +762,55 @@ impl Value {
             _ => panic!(),
         }
     }
+    fn test_unsigned(a: Value, b: Value, holds: fn(Ordering) -> bool) -> Value {
+        use Value::*;
+        match (a, b) {
+            (I1(a), I1(b)) => I1(holds(a.cmp(&b))),
+            (I8(a), I8(b)) => I1(holds(a.cmp(&b))),
+            (I64(a), I64(b)) => I1(holds(a.cmp(&b))),
+            _ => panic!(),
+        }
+    }
+    fn test_signed(a: Value, b: Value, holds: fn(Ordering) -> bool) -> Value {
+        use Value::*;
+        match (a, b) {
+            // A set i1 is -1 when read as signed.
+            (I1(a), I1(b)) => I1(holds(b.cmp(&a))),
+            (I8(a), I8(b)) => I1(holds((a as i8).cmp(&(b as i8)))),
+            (I64(a), I64(b)) => I1(holds((a as i64).cmp(&(b as i64)))),
+            _ => panic!(),
+        }
+    }
+
+    pub fn do_cast_op(a: Value, op: CastOp, to: Type) -> Value {
+        use Value::*;
+        let (bits, from) = match a {
+            Uninit => panic!(),
+            I1(a) => (a as u64, Type::I1),
+            I8(a) => (a as u64, Type::I8),
+            I64(a) => (a, Type::I64),
+        };
+        let bits = match op {
+            CastOp::ZExt => {
+                assert!(from.bits() <= to.bits(), "cannot zext {from} to {to}");
+                bits
+            }
+            CastOp::SExt => {
+                assert!(from.bits() <= to.bits(), "cannot sext {from} to {to}");
+                let unused = 64 - from.bits();
+                (((bits << unused) as i64) >> unused) as u64
+            }
+            CastOp::Trunc => {
+                assert!(from.bits() >= to.bits(), "cannot trunc {from} to {to}");
+                bits
+            }
+        };
+        match to {
+            Type::I1 => I1(bits & 1 != 0),
+            Type::I8 => I8(bits as u8),
+            Type::I64 => I64(bits),
+        }
+    }
 
     pub fn to_leaf_expr(self) -> LeafExpr {
         match self {
@@ -5,6 +5,7 @@ use std::{
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Nop,
 
@@ -16,6 +17,8 @@ pub enum Instruction {
 
     Output(LeafExpr),
     Input(RegisterID, LeafExpr),
+    /// Writes the cells around an index to stderr.
+    Dump(LeafExpr),
 
     Jump(TargetBlock),
     Branch(LeafExpr, TargetBlock, TargetBlock),
@@ -31,6 +34,7 @@ impl Instruction {
             Assign(_, e) => e.replace_usages(map),
             Output(e) => e.replace_usage(map),
             Input(_, e) => e.replace_usage(map),
+            Dump(e) => e.replace_usage(map),
             Jump(target) => target.replace_usages(map),
             Branch(c, t, e) => c.replace_usage(map) | t.replace_usages(map) | e.replace_usages(map),
         }
@@ -52,6 +56,7 @@ impl Instruction {
             Assign(_, e) => e.populate_used(used),
             Output(e) => e.populate_used(used),
             Input(_, e) => e.populate_used(used),
+            Dump(e) => e.populate_used(used),
             Jump(t) => t.populate_used(used),
             Branch(c, t, e) => {
                 c.populate_used(used);
@@ -70,6 +75,7 @@ impl Instruction {
             Self::Assign(_, e) => e.contains(reg),
             Self::Output(e) => e.contains(reg),
             Self::Input(_, e) => e.contains(reg),
+            Self::Dump(e) => e.contains(reg),
             Self::Jump(t) => t.uses(reg),
             Self::Branch(c, t, e) => c.contains(reg) || t.uses(reg) || e.uses(reg),
         }
@@ -77,6 +83,7 @@ impl Instruction {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOp {
     Add,
     Sub,
@@ -89,6 +96,11 @@ pub enum BinaryOp {
     And,
     Or,
     Xor,
+
+    /// Shifts by amounts of at least the bit width shift every bit out.
+    Shl,
+    LShr,
+    AShr,
 }
 impl Display for BinaryOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -104,11 +116,15 @@ impl Display for BinaryOp {
             And => write!(f, "and"),
             Or => write!(f, "or"),
             Xor => write!(f, "xor"),
+            Shl => write!(f, "shl"),
+            LShr => write!(f, "lshr"),
+            AShr => write!(f, "ashr"),
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOp {
     Not,
     Neg,
@@ -124,9 +140,14 @@ impl Display for UnaryOp {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TestOp {
     Equal,
     NotEqual,
+    ULessThan,
+    ULessEqual,
+    ILessThan,
+    ILessEqual,
 }
 impl Display for TestOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -134,11 +155,34 @@ impl Display for TestOp {
         match *self {
             Equal => write!(f, "teq"),
             NotEqual => write!(f, "tne"),
+            ULessThan => write!(f, "tult"),
+            ULessEqual => write!(f, "tule"),
+            ILessThan => write!(f, "tslt"),
+            ILessEqual => write!(f, "tsle"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CastOp {
+    ZExt,
+    SExt,
+    Trunc,
+}
+impl Display for CastOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use CastOp::*;
+        match *self {
+            ZExt => write!(f, "zext"),
+            SExt => write!(f, "sext"),
+            Trunc => write!(f, "trunc"),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TargetBlock {
     pub id: BlockID,
     pub args: Vec<LeafExpr>,
@@ -197,18 +241,21 @@ impl Display for TargetBlock {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Leaf(LeafExpr),
     Binary(LeafExpr, BinaryOp, LeafExpr),
     Unary(LeafExpr, UnaryOp),
     Test(LeafExpr, TestOp, LeafExpr),
+    /// Converts a value to the given type.
+    Cast(LeafExpr, CastOp, Type),
 }
 impl Expr {
     pub fn contains(self, reg: RegisterID) -> bool {
         match self {
             Self::Leaf(l) => l.contains(reg),
             Self::Binary(a, _, b) => a.contains(reg) || b.contains(reg),
-            Self::Unary(a, _) => a.contains(reg),
+            Self::Unary(a, _) | Self::Cast(a, _, _) => a.contains(reg),
             Self::Test(a, _, b) => a.contains(reg) || b.contains(reg),
         }
     }
@@ -220,7 +267,7 @@ impl Expr {
                 a.populate_used(used);
                 b.populate_used(used);
             }
-            Self::Unary(a, _) => a.populate_used(used),
+            Self::Unary(a, _) | Self::Cast(a, _, _) => a.populate_used(used),
             Self::Test(a, _, b) => {
                 a.populate_used(used);
                 b.populate_used(used);
@@ -240,6 +287,7 @@ impl Expr {
             }
             Self::Test(a, op, b) => Some(Value::do_test_op(a.eval_const()?, b.eval_const()?, op)),
             Self::Unary(a, op) => Some(Value::do_unary_op(a.eval_const()?, op)),
+            Self::Cast(a, op, ty) => Some(Value::do_cast_op(a.eval_const()?, op, ty)),
         }
     }
     pub fn replace_usages(&mut self, map: &HashMap<RegisterID, LeafExpr>) -> bool {
@@ -247,13 +295,14 @@ impl Expr {
         match self {
             Leaf(l) => l.replace_usage(map),
             Binary(a, _, b) => a.replace_usage(map) | b.replace_usage(map),
-            Unary(a, _) => a.replace_usage(map),
+            Unary(a, _) | Cast(a, _, _) => a.replace_usage(map),
             Test(a, _, b) => a.replace_usage(map) | b.replace_usage(map),
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LeafExpr {
     Register(RegisterID),
     Int(ConstInt),
@@ -290,6 +339,7 @@ impl LeafExpr {
         if let &mut Self::Register(reg) = self {
             if let Some(&new) = map.get(&reg) {
                 *self = new;
+                return true;
             }
         }
         false
@@ -309,9 +359,9 @@ impl LeafExpr {
     }
 
     pub fn expr_type(&self, module: &Module) -> Type {
-        match self {
-            &Self::Int(c) => c.int_type(),
-            &Self::Register(r) => module[r].register_type(),
+        match *self {
+            Self::Int(c) => c.int_type(),
+            Self::Register(r) => module[r].register_type(),
         }
     }
 }
@@ -360,6 +410,7 @@ impl Display for LeafExpr {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstInt {
     Bool(bool),
     I8(i8),
@@ -421,12 +472,12 @@ impl From<u64> for ConstInt {
 }
 impl Display for ConstInt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            &Self::Bool(v) => write!(f, "{v}"),
-            &Self::U8(v) => write!(f, "{v}"),
-            &Self::I8(v) => write!(f, "{v}"),
-            &Self::U64(v) => write!(f, "{v}"),
-            &Self::I64(v) => write!(f, "{v}"),
+        match *self {
+            Self::Bool(v) => write!(f, "{v}"),
+            Self::U8(v) => write!(f, "{v}"),
+            Self::I8(v) => write!(f, "{v}"),
+            Self::U64(v) => write!(f, "{v}"),
+            Self::I64(v) => write!(f, "{v}"),
         }
     }
 }
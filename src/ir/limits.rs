@@ -0,0 +1,152 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io,
+    time::{Duration, Instant},
+};
+
+/// How many blocks run between two looks at the clock.
+const DEADLINE_INTERVAL: u64 = 1 << 12;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of blocks (or loop iterations) to enter.
+    pub fuel: Option<u64>,
+    pub timeout: Option<Duration>,
+    /// Maximum number of tape cells.
+    pub max_tape: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub blocks: u64,
+    pub instructions: u64,
+    pub elapsed: Duration,
+    pub tape: usize,
+}
+impl Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} blocks, {} instructions, {} cells in {:.3?}",
+            self.blocks, self.instructions, self.tape, self.elapsed
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ExecError {
+    Io(io::Error),
+    OutOfFuel(Progress),
+    Timeout(Progress),
+    TapeLimit(Progress),
+    /// The pointer moved left of cell 0.
+    LeftOfTape(Progress),
+}
+impl ExecError {
+    pub fn progress(&self) -> Option<Progress> {
+        match *self {
+            Self::Io(_) => None,
+            Self::OutOfFuel(p) | Self::Timeout(p) | Self::TapeLimit(p) | Self::LeftOfTape(p) => {
+                Some(p)
+            }
+        }
+    }
+}
+impl Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "i/o error: {err}"),
+            Self::OutOfFuel(p) => write!(f, "out of fuel after {p}"),
+            Self::Timeout(p) => write!(f, "timed out after {p}"),
+            Self::TapeLimit(p) => write!(f, "tape limit exceeded after {p}"),
+            Self::LeftOfTape(p) => write!(f, "pointer moved left of cell 0 after {p}"),
+        }
+    }
+}
+impl Error for ExecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+impl From<io::Error> for ExecError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Counts executed blocks and instructions against a set of [`Limits`].
+#[derive(Clone, Debug)]
+pub struct Meter {
+    limits: Limits,
+    start: Instant,
+    blocks: u64,
+    instructions: u64,
+}
+impl Meter {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            start: Instant::now(),
+            blocks: 0,
+            instructions: 0,
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+    pub fn reset(&mut self) {
+        self.start = Instant::now();
+        self.blocks = 0;
+        self.instructions = 0;
+    }
+
+    /// Carries on counting from `progress`, as if the time since had not passed.
+    pub fn resume(&mut self, progress: Progress) {
+        self.start = Instant::now()
+            .checked_sub(progress.elapsed)
+            .unwrap_or_else(Instant::now);
+        self.blocks = progress.blocks;
+        self.instructions = progress.instructions;
+    }
+
+    pub fn tick(&mut self, instructions: u64, tape: usize) -> Result<(), ExecError> {
+        self.blocks += 1;
+        self.instructions += instructions;
+        if self.limits.fuel.is_some_and(|fuel| self.blocks > fuel) {
+            return Err(ExecError::OutOfFuel(self.progress(tape)));
+        }
+        if self.blocks.is_multiple_of(DEADLINE_INTERVAL) {
+            if let Some(timeout) = self.limits.timeout {
+                if self.start.elapsed() > timeout {
+                    return Err(ExecError::Timeout(self.progress(tape)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tape_limit(&self, tape: usize) -> ExecError {
+        ExecError::TapeLimit(self.progress(tape))
+    }
+    pub fn left_of_tape(&self, tape: usize) -> ExecError {
+        ExecError::LeftOfTape(self.progress(tape))
+    }
+    pub fn progress(&self, tape: usize) -> Progress {
+        Progress {
+            blocks: self.blocks,
+            instructions: self.instructions,
+            elapsed: self.start.elapsed(),
+            tape,
+        }
+    }
+}
+impl Default for Meter {
+    fn default() -> Self {
+        Self::new(Limits::default())
+    }
+}
@@ -1,22 +1,58 @@
 use super::{
     instruction::{BinaryOp, Expr, Instruction, LeafExpr, UnaryOp},
+    printing::Printer,
     register::RegisterID,
     Module,
 };
-use std::collections::{HashMap, HashSet};
+use crate::pass::{OptLevel, PassManager, PassTarget};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+};
+
+pub const MAX_ITERATIONS: usize = 64;
 
 pub fn optimize_module(module: &mut Module) {
-    let mut changed = true;
-    while changed {
-        changed = false;
-        changed |= local_cse(module);
-        changed |= remove_identity_muls(module);
-        changed |= remove_negating_muls(module);
-        changed |= do_constant_operations(module);
-        changed |= propagate_leaf_assigns(module);
-        changed |= remove_dead_assignments(module);
-
-        remove_nops(module);
+    pass_manager(OptLevel::O2).run(module);
+}
+
+type ModulePass = (&'static str, fn(&mut Module) -> bool);
+
+pub fn pass_manager(level: OptLevel) -> PassManager<Module> {
+    let passes: &[ModulePass] = match level {
+        OptLevel::O0 => &[],
+        OptLevel::O1 => &[
+            ("do-constant-operations", do_constant_operations),
+            ("propagate-leaf-assigns", propagate_leaf_assigns),
+            ("remove-dead-assignments", remove_dead_assignments),
+            ("remove-nops", remove_nops),
+        ],
+        OptLevel::O2 => &[
+            ("local-cse", local_cse),
+            ("remove-identity-muls", remove_identity_muls),
+            ("remove-negating-muls", remove_negating_muls),
+            ("do-constant-operations", do_constant_operations),
+            ("propagate-leaf-assigns", propagate_leaf_assigns),
+            ("remove-dead-assignments", remove_dead_assignments),
+            ("remove-nops", remove_nops),
+        ],
+    };
+
+    let mut manager = PassManager::new();
+    for &(name, pass) in passes {
+        manager.add_pass(name, pass);
+    }
+    if level == OptLevel::O2 {
+        manager.set_max_iterations(MAX_ITERATIONS);
+    }
+    manager
+}
+impl PassTarget for Module {
+    fn instruction_count(&self) -> usize {
+        self.blocks.iter().map(|b| b.body.len()).sum()
+    }
+    fn dump(&self, out: &mut dyn Write) -> io::Result<()> {
+        Printer::new(out).print_module(self)
     }
 }
 
@@ -120,7 +156,7 @@ pub fn propagate_leaf_assigns(module: &mut Module) -> bool {
 
 pub fn remove_dead_assignments(module: &mut Module) -> bool {
     let mut not_dead = HashSet::new();
-    let instructions = module.blocks.iter().map(|b| b.body.iter()).flatten();
+    let instructions = module.blocks.iter().flat_map(|b| b.body.iter());
     instructions.for_each(|i| i.populate_used(&mut not_dead));
 
     let mut changed = false;
@@ -139,8 +175,13 @@ pub fn remove_dead_assignments(module: &mut Module) -> bool {
     changed
 }
 
-pub fn remove_nops(module: &mut Module) {
+pub fn remove_nops(module: &mut Module) -> bool {
+    let mut changed = false;
     for block in &mut module.blocks {
+        let before = block.body.len();
         block.body.retain(|i| i != &Instruction::Nop);
+        changed |= block.body.len() != before;
     }
+
+    changed
 }
@@ -1,7 +1,8 @@
 use super::{
     block::Block,
-    instruction::{BinaryOp, Instruction, LeafExpr, TestOp, UnaryOp},
+    instruction::{BinaryOp, CastOp, Instruction, LeafExpr, TestOp, UnaryOp},
     register::RegisterID,
+    types::Type,
     Module,
 };
 use crate::ir::instruction::Expr;
@@ -21,7 +22,7 @@ impl<O: Write> Printer<O> {
         }
         Ok(())
     }
-    fn print_block(&mut self, b: &Block, m: &Module) -> io::Result<()> {
+    pub(super) fn print_block(&mut self, b: &Block, m: &Module) -> io::Result<()> {
         write!(self.out, "{}", b.id())?;
         if let Some((&last, others)) = b.parameters().split_last() {
             write!(self.out, "(")?;
@@ -56,11 +57,13 @@ impl<O: Write> Printer<O> {
                     Expr::Binary(a, op, b) => self.print_bin_op(a, op, b, m)?,
                     Expr::Unary(a, op) => self.print_un_op(a, op, m)?,
                     Expr::Test(a, op, b) => self.print_test_op(a, op, b, m)?,
+                    Expr::Cast(a, op, ty) => self.print_cast_op(a, op, ty, m)?,
                 };
                 writeln!(self.out)?;
             }
             &Output(value) => writeln!(self.out, "stdout << {value}")?,
             &Input(target, default) => writeln!(self.out, "{target} = eof ? {default} : stdin")?,
+            &Dump(index) => writeln!(self.out, "stderr << dump({index})")?,
             Jump(target) => writeln!(self.out, "jump {target}")?,
             Branch(condition, then, els) => {
                 writeln!(self.out, "branch {condition}\n\t  {then}\n\t  {els}")?
@@ -97,6 +100,10 @@ impl<O: Write> Printer<O> {
         let a_type = a.expr_type(m);
         write!(self.out, "{op} {a_type} {a}, {b}")
     }
+    fn print_cast_op(&mut self, a: LeafExpr, op: CastOp, ty: Type, m: &Module) -> io::Result<()> {
+        let a_type = a.expr_type(m);
+        write!(self.out, "{op} {a_type} {a} to {ty}")
+    }
 
     fn print_reg_with_type(&mut self, reg: RegisterID, m: &Module) -> io::Result<()> {
         let rt = m[reg].register_type();
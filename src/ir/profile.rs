@@ -0,0 +1,42 @@
+use super::{block::BlockID, Module};
+
+/// Execution counts gathered by [`Exec`](super::exec::Exec) while profiling.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// How often each block was entered.
+    pub blocks: Vec<u64>,
+    /// How often each instruction of each block ran.
+    pub instructions: Vec<Vec<u64>>,
+    pub output_bytes: u64,
+    /// One past the highest cell loaded or stored.
+    pub tape_high_water: usize,
+}
+impl Profile {
+    pub fn new(module: &Module) -> Self {
+        Self {
+            blocks: vec![0; module.blocks.len()],
+            instructions: module
+                .blocks
+                .iter()
+                .map(|b| vec![0; b.body().len()])
+                .collect(),
+            output_bytes: 0,
+            tape_high_water: 0,
+        }
+    }
+
+    pub fn block_entries(&self, block: BlockID) -> u64 {
+        self.blocks[block.0]
+    }
+    pub fn block_instructions(&self, block: BlockID) -> u64 {
+        self.instructions[block.0].iter().sum()
+    }
+    pub fn total_instructions(&self) -> u64 {
+        self.instructions.iter().flatten().sum()
+    }
+
+    pub fn instructions_per_output_byte(&self) -> Option<f64> {
+        (self.output_bytes != 0)
+            .then(|| self.total_instructions() as f64 / self.output_bytes as f64)
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::Display;
 use super::types::Type;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Register {
     id: RegisterID,
     register_type: Type,
@@ -18,12 +19,18 @@ impl Register {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RegisterID(pub(super) usize);
 impl From<usize> for RegisterID {
     fn from(value: usize) -> Self {
         Self(value)
     }
 }
+impl From<RegisterID> for usize {
+    fn from(value: RegisterID) -> Self {
+        value.0
+    }
+}
 impl Display for RegisterID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "%{}", self.0)
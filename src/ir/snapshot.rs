@@ -0,0 +1,198 @@
+use super::{
+    block::BlockID,
+    exec::{Position, Value},
+    limits::Progress,
+    register::RegisterID,
+    types::Type,
+    Module,
+};
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+const MAGIC: &[u8; 4] = b"BFSN";
+const VERSION: u8 = 2;
+
+/// The complete state of a paused [`Exec`](super::exec::Exec).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub position: Position,
+    pub registers: Vec<Value>,
+    pub cells: Vec<u8>,
+    /// What the [`Meter`](super::limits::Meter) had counted so far.
+    pub progress: Progress,
+}
+impl Snapshot {
+    /// Checks that the position, registers and their values fit `module`, so
+    /// a snapshot taken from a different program is rejected instead of
+    /// crashing.
+    pub fn validate(&self, module: &Module) -> io::Result<()> {
+        if self.registers.len() != module.registers.len() {
+            return Err(invalid(&format!(
+                "snapshot has {} registers, the module {}",
+                self.registers.len(),
+                module.registers.len()
+            )));
+        }
+        for (i, &value) in self.registers.iter().enumerate() {
+            let id = RegisterID::from(i);
+            check_type(id, value, module[id].register_type())?;
+        }
+        let block = |id: BlockID| {
+            module
+                .block(id)
+                .ok_or_else(|| invalid(&format!("no block {id} in the module")))
+        };
+        match &self.position {
+            Position::Halted => (),
+            Position::Jump(id, args) => {
+                let params = block(*id)?.parameters();
+                if params.len() != args.len() {
+                    return Err(invalid(&format!("wrong number of arguments for {id}")));
+                }
+                for (&param, &arg) in params.iter().zip(args) {
+                    check_type(param, arg, module[param].register_type())?;
+                }
+            }
+            &Position::At(id, index) => {
+                if index > block(id)?.body().len() {
+                    return Err(invalid(&format!("no instruction {index} in {id}")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+
+        match &self.position {
+            Position::Halted => w.write_all(&[0])?,
+            Position::Jump(block, args) => {
+                w.write_all(&[1])?;
+                write_u64(&mut w, block.0 as u64)?;
+                write_values(&mut w, args)?;
+            }
+            &Position::At(block, index) => {
+                w.write_all(&[2])?;
+                write_u64(&mut w, block.0 as u64)?;
+                write_u64(&mut w, index as u64)?;
+            }
+        }
+        write_values(&mut w, &self.registers)?;
+        write_u64(&mut w, self.cells.len() as u64)?;
+        w.write_all(&self.cells)?;
+        write_u64(&mut w, self.progress.blocks)?;
+        write_u64(&mut w, self.progress.instructions)?;
+        write_u64(&mut w, self.progress.elapsed.as_nanos() as u64)
+    }
+
+    pub fn read_from(mut r: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u8(&mut r)? != VERSION {
+            return Err(invalid("not a snapshot"));
+        }
+
+        let position = match read_u8(&mut r)? {
+            0 => Position::Halted,
+            1 => {
+                let block = BlockID::from(read_u64(&mut r)? as usize);
+                Position::Jump(block, read_values(&mut r)?)
+            }
+            2 => {
+                let block = BlockID::from(read_u64(&mut r)? as usize);
+                Position::At(block, read_u64(&mut r)? as usize)
+            }
+            tag => return Err(invalid(&format!("unknown position tag {tag}"))),
+        };
+        let registers = read_values(&mut r)?;
+        let len = read_u64(&mut r)?;
+        let mut cells = Vec::new();
+        r.by_ref().take(len).read_to_end(&mut cells)?;
+        if cells.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let progress = Progress {
+            blocks: read_u64(&mut r)?,
+            instructions: read_u64(&mut r)?,
+            elapsed: Duration::from_nanos(read_u64(&mut r)?),
+            tape: cells.len(),
+        };
+
+        Ok(Self {
+            position,
+            registers,
+            cells,
+            progress,
+        })
+    }
+}
+
+/// Uninitialized values are allowed anywhere, as they are in a fresh [`Exec`].
+///
+/// [`Exec`]: super::exec::Exec
+fn check_type(register: RegisterID, value: Value, ty: Type) -> io::Result<()> {
+    let fits = matches!(
+        (value, ty),
+        (Value::Uninit, _)
+            | (Value::I1(_), Type::I1)
+            | (Value::I8(_), Type::I8)
+            | (Value::I64(_), Type::I64)
+    );
+    if !fits {
+        return Err(invalid(&format!("{register} holds {value:?}, not an {ty}")));
+    }
+    Ok(())
+}
+
+fn write_u64(w: &mut impl Write, value: u64) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+fn write_values(w: &mut impl Write, values: &[Value]) -> io::Result<()> {
+    write_u64(w, values.len() as u64)?;
+    for &value in values {
+        let (tag, payload) = match value {
+            Value::Uninit => (0, 0),
+            Value::I1(b) => (1, b as u64),
+            Value::I8(v) => (2, v as u64),
+            Value::I64(v) => (3, v),
+        };
+        w.write_all(&[tag])?;
+        write_u64(w, payload)?;
+    }
+    Ok(())
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buffer = [0];
+    r.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    r.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+fn read_values(r: &mut impl Read) -> io::Result<Vec<Value>> {
+    let len = read_u64(r)?;
+    (0..len)
+        .map(|_| {
+            let tag = read_u8(r)?;
+            let payload = read_u64(r)?;
+            Ok(match tag {
+                0 => Value::Uninit,
+                1 => Value::I1(payload != 0),
+                2 => Value::I8(payload as u8),
+                3 => Value::I64(payload),
+                _ => return Err(invalid(&format!("unknown value tag {tag}"))),
+            })
+        })
+        .collect()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,465 @@
+use super::{block::BlockID, exec::Value, register::RegisterID};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+const MAGIC: &[u8; 4] = b"BFTR";
+const VERSION: u8 = 1;
+
+/// One step of a recorded execution.
+///
+/// Instructions are not recorded themselves: after an [`Event::Enter`] the
+/// block's instructions run in order, so the register writes following it are
+/// first the block's parameters and then the results of its instructions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Enter(BlockID),
+    Register(RegisterID, Value),
+    Store { cell: u64, value: u8 },
+    Output(u8),
+    Input(u8),
+}
+
+/// Streams events to a compact binary trace.
+pub struct TraceWriter {
+    out: BufWriter<Box<dyn Write + Send>>,
+}
+impl TraceWriter {
+    pub fn new(out: impl Write + Send + 'static) -> io::Result<Self> {
+        let mut out = BufWriter::new(Box::new(out) as Box<dyn Write + Send>);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, event: Event) -> io::Result<()> {
+        let out = &mut self.out;
+        match event {
+            Event::Enter(block) => {
+                out.write_all(&[0])?;
+                write_varint(out, usize::from(block) as u64)
+            }
+            Event::Register(register, value) => {
+                out.write_all(&[1])?;
+                write_varint(out, usize::from(register) as u64)?;
+                let (tag, payload) = match value {
+                    Value::Uninit => (0, 0),
+                    Value::I1(b) => (1, b as u64),
+                    Value::I8(v) => (2, v as u64),
+                    Value::I64(v) => (3, v),
+                };
+                out.write_all(&[tag])?;
+                write_varint(out, payload)
+            }
+            Event::Store { cell, value } => {
+                out.write_all(&[2])?;
+                write_varint(out, cell)?;
+                out.write_all(&[value])
+            }
+            Event::Output(byte) => out.write_all(&[3, byte]),
+            Event::Input(byte) => out.write_all(&[4, byte]),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Reads the events of a trace written by [`TraceWriter`].
+pub struct TraceReader<R> {
+    input: BufReader<R>,
+}
+impl<R: Read> TraceReader<R> {
+    pub fn new(input: R) -> io::Result<Self> {
+        let mut input = BufReader::new(input);
+        let mut header = [0; 5];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid("not a trace"));
+        }
+        Ok(Self { input })
+    }
+
+    fn read_event(&mut self) -> io::Result<Option<Event>> {
+        let mut tag = [0];
+        if self.input.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        let input = &mut self.input;
+        Ok(Some(match tag[0] {
+            0 => Event::Enter(BlockID::from(read_varint(input)? as usize)),
+            1 => {
+                let register = RegisterID::from(read_varint(input)? as usize);
+                let tag = read_u8(input)?;
+                let payload = read_varint(input)?;
+                let value = match tag {
+                    0 => Value::Uninit,
+                    1 => Value::I1(payload != 0),
+                    2 => Value::I8(payload as u8),
+                    3 => Value::I64(payload),
+                    _ => return Err(invalid(&format!("unknown value tag {tag}"))),
+                };
+                Event::Register(register, value)
+            }
+            2 => Event::Store {
+                cell: read_varint(input)?,
+                value: read_u8(input)?,
+            },
+            3 => Event::Output(read_u8(input)?),
+            4 => Event::Input(read_u8(input)?),
+            tag => return Err(invalid(&format!("unknown event tag {tag}"))),
+        }))
+    }
+}
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_event().transpose()
+    }
+}
+
+/// The observable effects of a trace: what it left on the tape and wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Replay {
+    pub tape: Vec<u8>,
+    pub output: Vec<u8>,
+    pub input: Vec<u8>,
+    /// For every cell, how many bytes had been output when it was last
+    /// written, if it was written at all.
+    pub last_write: Vec<Option<u64>>,
+}
+impl Replay {
+    pub fn new(events: impl IntoIterator<Item = io::Result<Event>>) -> io::Result<Self> {
+        let mut replay = Self::default();
+        for event in events {
+            replay.apply(event?);
+        }
+        Ok(replay)
+    }
+
+    pub fn apply(&mut self, event: Event) {
+        match event {
+            Event::Enter(_) | Event::Register(..) => (),
+            Event::Store { cell, value } => {
+                let cell = cell as usize;
+                if cell >= self.tape.len() {
+                    self.tape.resize(cell + 1, 0);
+                    self.last_write.resize(cell + 1, None);
+                }
+                self.tape[cell] = value;
+                self.last_write[cell] = Some(self.output.len() as u64);
+            }
+            Event::Output(byte) => self.output.push(byte),
+            Event::Input(byte) => self.input.push(byte),
+        }
+    }
+
+    fn cell(&self, cell: usize) -> u8 {
+        self.tape.get(cell).copied().unwrap_or(0)
+    }
+}
+
+/// How the effects of two runs of the same program differ.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The byte at `position` of the output differs, or one run stopped early.
+    Output {
+        position: u64,
+        left: Option<u8>,
+        right: Option<u8>,
+    },
+    /// Both runs consumed a different amount of input.
+    Input { left: u64, right: u64 },
+    /// The `index`th store that changed `cell` differs, or only one run made
+    /// it. `left_after` and `right_after` are the number of output bytes each
+    /// run had written when it made the store.
+    Store {
+        cell: u64,
+        index: u64,
+        left: Option<u8>,
+        right: Option<u8>,
+        left_after: Option<u64>,
+        right_after: Option<u64>,
+    },
+    /// A cell ended up with different values. `left_after` and `right_after`
+    /// are the number of output bytes each run had written when it last
+    /// stored to the cell, or `None` if it never did.
+    Cell {
+        cell: u64,
+        left: u8,
+        right: u8,
+        left_after: Option<u64>,
+        right_after: Option<u64>,
+    },
+}
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let byte = |b: Option<u8>| b.map_or("nothing".to_owned(), |b| b.to_string());
+        match *self {
+            Self::Output {
+                position,
+                left,
+                right,
+            } => write!(
+                f,
+                "output byte {position} differs: {} vs {}",
+                byte(left),
+                byte(right)
+            ),
+            Self::Input { left, right } => {
+                write!(f, "input consumed differs: {left} vs {right} bytes")
+            }
+            Self::Store {
+                cell,
+                index,
+                left,
+                right,
+                left_after,
+                right_after,
+            } => {
+                let after = |a: Option<u64>| a.map_or("-".to_owned(), |a| a.to_string());
+                write!(
+                    f,
+                    "store {index} to cell {cell} differs: {} vs {}, after {} vs {} output bytes",
+                    byte(left),
+                    byte(right),
+                    after(left_after),
+                    after(right_after)
+                )
+            }
+            Self::Cell {
+                cell,
+                left,
+                right,
+                left_after,
+                right_after,
+            } => {
+                let after = |a: Option<u64>| {
+                    a.map_or("never written".to_owned(), |a| {
+                        format!("last written after {a} output bytes")
+                    })
+                };
+                write!(
+                    f,
+                    "cell {cell} ends up {left} vs {right}, {} vs {}",
+                    after(left_after),
+                    after(right_after)
+                )
+            }
+        }
+    }
+}
+
+/// Walks two traces in step and reports where they first diverge.
+///
+/// The traces are compared output byte by output byte, and every cell's
+/// sequence of stores is compared as the stores happen; stores that leave a
+/// cell unchanged are skipped. Within the stretch up to the same output byte,
+/// a differing store is reported before the byte, as it is the likelier
+/// cause. Traces that agree on both are compared by [`diff_end_states`].
+///
+/// Optimized code stores to cells at different times, and often fewer times,
+/// than unoptimized code, so diffing traces from different optimization levels
+/// reports stores that the optimizer legitimately removed; use
+/// [`diff_end_states`] for those.
+pub fn diff(
+    left: impl IntoIterator<Item = io::Result<Event>>,
+    right: impl IntoIterator<Item = io::Result<Event>>,
+) -> io::Result<Option<Divergence>> {
+    let mut left = Walk::new(left.into_iter());
+    let mut right = Walk::new(right.into_iter());
+
+    loop {
+        let position = left.replay.output.len() as u64;
+        let left_byte = left.next_output()?;
+        let right_byte = right.next_output()?;
+
+        let mut diverged = None;
+        for (&cell, lefts) in &mut left.pending {
+            let Some(rights) = right.pending.get_mut(&cell) else {
+                continue;
+            };
+            while let (Some(&l), Some(&r)) = (lefts.front(), rights.front()) {
+                lefts.pop_front();
+                rights.pop_front();
+                if l.value != r.value {
+                    diverged = earlier(diverged, store_divergence(cell, Some(l), Some(r)));
+                    break;
+                }
+            }
+        }
+        if let Some((_, divergence)) = diverged {
+            return Ok(Some(divergence));
+        }
+
+        if left_byte != right_byte {
+            return Ok(Some(Divergence::Output {
+                position,
+                left: left_byte,
+                right: right_byte,
+            }));
+        }
+        if left_byte.is_none() {
+            break;
+        }
+    }
+
+    let mut diverged = None;
+    for (&cell, stores) in &left.pending {
+        if let Some(&store) = stores.front() {
+            diverged = earlier(diverged, store_divergence(cell, Some(store), None));
+        }
+    }
+    for (&cell, stores) in &right.pending {
+        if let Some(&store) = stores.front() {
+            diverged = earlier(diverged, store_divergence(cell, None, Some(store)));
+        }
+    }
+    match diverged {
+        Some((_, divergence)) => Ok(Some(divergence)),
+        None => Ok(diff_end_states(&left.replay, &right.replay)),
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Store {
+    index: u64,
+    value: u8,
+    after: u64,
+}
+
+/// One side of [`diff`]: the effects so far and the stores the other side
+/// has not made yet.
+struct Walk<I> {
+    events: I,
+    replay: Replay,
+    stores: HashMap<u64, u64>,
+    pending: HashMap<u64, VecDeque<Store>>,
+}
+impl<I: Iterator<Item = io::Result<Event>>> Walk<I> {
+    fn new(events: I) -> Self {
+        Self {
+            events,
+            replay: Replay::default(),
+            stores: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Runs up to and including the next output, returning its byte.
+    fn next_output(&mut self) -> io::Result<Option<u8>> {
+        for event in self.events.by_ref() {
+            let event = event?;
+            match event {
+                Event::Store { cell, value } if self.replay.cell(cell as usize) != value => {
+                    let index = self.stores.entry(cell).or_insert(0);
+                    self.pending.entry(cell).or_default().push_back(Store {
+                        index: *index,
+                        value,
+                        after: self.replay.output.len() as u64,
+                    });
+                    *index += 1;
+                }
+                _ => (),
+            }
+            self.replay.apply(event);
+            if let Event::Output(byte) = event {
+                return Ok(Some(byte));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Orders divergent stores by how much output came before them, then by cell.
+type Ranked = ((u64, u64), Divergence);
+
+fn store_divergence(cell: u64, left: Option<Store>, right: Option<Store>) -> Ranked {
+    let store = left.or(right).unwrap();
+    let after = left.iter().chain(&right).map(|s| s.after).min().unwrap();
+    let divergence = Divergence::Store {
+        cell,
+        index: store.index,
+        left: left.map(|s| s.value),
+        right: right.map(|s| s.value),
+        left_after: left.map(|s| s.after),
+        right_after: right.map(|s| s.after),
+    };
+    ((after, cell), divergence)
+}
+fn earlier(a: Option<Ranked>, b: Ranked) -> Option<Ranked> {
+    match a {
+        Some(a) if a.0 <= b.0 => Some(a),
+        _ => Some(b),
+    }
+}
+
+/// Compares what two traces wrote, read and left on the tape once they had
+/// finished.
+///
+/// Only end states are compared: optimized code stores to cells at different
+/// times than unoptimized code, so intermediate values say nothing, and a cell
+/// that differed along the way but ended up the same is not reported. The
+/// first differing output byte wins over the lowest differing cell, which wins
+/// over the amount of input consumed.
+pub fn diff_end_states(left: &Replay, right: &Replay) -> Option<Divergence> {
+    let written = |r: &Replay, i: usize| r.last_write.get(i).copied().flatten();
+    let output = (0..left.output.len().max(right.output.len()))
+        .find(|&i| left.output.get(i) != right.output.get(i));
+    let cell = (0..left.tape.len().max(right.tape.len())).find(|&i| left.cell(i) != right.cell(i));
+
+    match (output, cell) {
+        (Some(i), _) => Some(Divergence::Output {
+            position: i as u64,
+            left: left.output.get(i).copied(),
+            right: right.output.get(i).copied(),
+        }),
+        (None, Some(i)) => Some(Divergence::Cell {
+            cell: i as u64,
+            left: left.cell(i),
+            right: right.cell(i),
+            left_after: written(left, i),
+            right_after: written(right, i),
+        }),
+        _ if left.input.len() != right.input.len() => Some(Divergence::Input {
+            left: left.input.len() as u64,
+            right: right.input.len() as u64,
+        }),
+        _ => None,
+    }
+}
+
+fn write_varint(w: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(r)?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint is too long"))
+}
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buffer = [0];
+    r.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
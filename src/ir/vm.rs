@@ -0,0 +1,89 @@
+use super::{
+    bytecode::{Bytecode, Op},
+    exec::{dump_tape, Io, Tape},
+    limits::{ExecError, Limits, Meter},
+};
+use std::io::{stderr, Read, Write};
+
+pub struct Vm<O, I> {
+    tape: Tape,
+    registers: Vec<u64>,
+    io: Io<O, I>,
+    meter: Meter,
+}
+impl<O: Write, I: Read> Vm<O, I> {
+    pub fn new(stdout: O, stdin: I) -> Self {
+        Self {
+            tape: Tape::new(),
+            registers: Vec::new(),
+            io: Io::new(stdout, stdin),
+            meter: Meter::default(),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.tape.set_max_len(limits.max_tape);
+        self.meter = Meter::new(limits);
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        self.tape.cells()
+    }
+
+    pub fn exec(&mut self, code: &Bytecode) -> Result<(), ExecError> {
+        self.meter.reset();
+        self.registers.clear();
+        self.registers.resize(code.registers, 0);
+        for &(reg, value) in &code.constants {
+            self.registers[reg as usize] = value;
+        }
+
+        let r = &mut self.registers;
+        let mut pc = 0;
+        loop {
+            match code.ops[pc] {
+                Op::Enter(instructions) => self.meter.tick(instructions as u64, self.tape.len())?,
+                Op::Load(dst, index) => {
+                    r[dst as usize] = self.tape[r[index as usize] as usize] as u64
+                }
+                Op::Store(index, value) => {
+                    self.tape[r[index as usize] as usize] = r[value as usize] as u8
+                }
+                Op::Grow(start, end) => {
+                    if (r[start as usize] as i64) < 0 {
+                        return Err(self.meter.left_of_tape(self.tape.len()));
+                    }
+                    if !self.tape.grow_to(r[end as usize] as usize) {
+                        return Err(self.meter.tape_limit(self.tape.len()));
+                    }
+                }
+                Op::Output(value) => self.io.write(r[value as usize] as u8)?,
+                Op::Input(dst, default) => {
+                    r[dst as usize] = self.io.read(r[default as usize] as u8)? as u64
+                }
+                Op::Dump(index) => dump_tape(
+                    stderr().lock(),
+                    self.tape.cells(),
+                    r[index as usize] as isize,
+                )?,
+                Op::Move(dst, src) => r[dst as usize] = r[src as usize],
+                Op::Binary(f, dst, a, b) => r[dst as usize] = f(r[a as usize], r[b as usize]),
+                Op::Unary(f, dst, a) => r[dst as usize] = f(r[a as usize]),
+                Op::Equal(dst, a, b) => r[dst as usize] = (r[a as usize] == r[b as usize]) as u64,
+                Op::NotEqual(dst, a, b) => {
+                    r[dst as usize] = (r[a as usize] != r[b as usize]) as u64
+                }
+                Op::Jump(target) => {
+                    pc = target as usize;
+                    continue;
+                }
+                Op::Branch(c, then, els) => {
+                    pc = if r[c as usize] != 0 { then } else { els } as usize;
+                    continue;
+                }
+                Op::Halt => return Ok(()),
+            }
+            pc += 1;
+        }
+    }
+}
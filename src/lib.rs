@@ -1,3 +1,4 @@
 pub mod frontend;
 pub mod ir;
+pub mod pass;
 pub mod util;
@@ -1,22 +1,479 @@
 use rustfck::{
-    frontend::{code_gen::gen_program, lexer::lex, optimize::apply_optimizations, parser::parse},
-    ir::{exec::Exec, optimize::optimize_module, printing::Printer},
+    frontend::{
+        code_gen::gen_program_with_loops,
+        debugger::{self, Debugger},
+        emit::emit,
+        exec::TreeExec,
+        format::{format, minify, FormatOptions},
+        lexer::{lex_spanned, lex_with, Dialect},
+        lint::lint,
+        optimize,
+        parser::try_parse,
+        profile::{write_folded, write_report},
+    },
+    ir::{
+        self,
+        bytecode::lower,
+        dot::{DotOptions, DotWriter},
+        exec::Exec,
+        limits::Limits,
+        printing::Printer,
+        trace::{diff, diff_end_states, Replay, TraceReader},
+        vm::Vm,
+    },
+    pass::OptLevel,
 };
-use std::io::{stdin, stdout, Cursor};
+use std::{
+    env::args,
+    fs::File,
+    io::{stderr, stdin, stdout, Cursor, Read},
+    iter::Peekable,
+    process::exit,
+    str::FromStr,
+    time::Duration,
+};
+
+const USAGE: &str = "usage: rustfck [-O0|-O1|-O2] [--time-passes] [--dump-after <pass>] \
+                     [--disable <pass>] [--print-ir] [--print-dot] \
+                     [--dot-loops] [--dot-dominators] [--emit-json ast|expr-tree|ir] [--emit-bf] [--interpret] [--fuel <blocks>] \
+                     [--timeout <ms>] [--max-tape <cells>] \
+                     [--debug-dump] [--input-separator] [--profile] \
+                     [--profile-folded <file>] [--trace <file>] [file]
+       rustfck debug [--input <file>] [--fuel <steps>] <file>
+       rustfck fmt [--width <columns>] [--indent <spaces>] [--strip-comments] \
+                   [--debug-dump] [--input-separator] [--write] [file...]
+       rustfck minify [--debug-dump] [--input-separator] [--write] [file...]
+       rustfck lint [--debug-dump] [--input-separator] <file...>
+       rustfck trace-diff [--end-state] <trace> <trace>";
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Stage {
+    Ast,
+    ExprTree,
+    Ir,
+}
+
+struct Options {
+    level: OptLevel,
+    time_passes: bool,
+    dump_after: Vec<String>,
+    disabled: Vec<String>,
+    print_ir: bool,
+    print_dot: bool,
+    dot: DotOptions,
+    emit_json: Option<Stage>,
+    emit_bf: bool,
+    interpret: bool,
+    limits: Limits,
+    dialect: Dialect,
+    profile: bool,
+    profile_folded: Option<String>,
+    trace: Option<String>,
+    path: String,
+}
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            level: OptLevel::O2,
+            time_passes: false,
+            dump_after: Vec::new(),
+            disabled: Vec::new(),
+            print_ir: false,
+            print_dot: false,
+            dot: DotOptions::default(),
+            emit_json: None,
+            emit_bf: false,
+            interpret: false,
+            limits: Limits::default(),
+            dialect: Dialect::default(),
+            profile: false,
+            profile_folded: None,
+            trace: None,
+            path: "./programs/mandelbrot.b".to_owned(),
+        };
+
+        while let Some(arg) = args.next() {
+            if let Some(level) = OptLevel::parse(&arg) {
+                options.level = level;
+                continue;
+            }
+            match arg.as_str() {
+                "--time-passes" => options.time_passes = true,
+                "--print-ir" => options.print_ir = true,
+                "--print-dot" => options.print_dot = true,
+                "--dot-loops" => (options.print_dot, options.dot.loops) = (true, true),
+                "--dot-dominators" => (options.print_dot, options.dot.dominators) = (true, true),
+                "--emit-json" => {
+                    options.emit_json = Some(match expect_value(&mut args, &arg)?.as_str() {
+                        "ast" => Stage::Ast,
+                        "expr-tree" => Stage::ExprTree,
+                        "ir" => Stage::Ir,
+                        stage => return Err(format!("unknown stage {stage}")),
+                    })
+                }
+                "--emit-bf" => options.emit_bf = true,
+                "--interpret" => options.interpret = true,
+                "--debug-dump" => options.dialect.debug_dump = true,
+                "--input-separator" => options.dialect.input_separator = true,
+                "--profile" => options.profile = true,
+                "--profile-folded" => options.profile_folded = Some(expect_value(&mut args, &arg)?),
+                "--trace" => options.trace = Some(expect_value(&mut args, &arg)?),
+                "--dump-after" => options.dump_after.push(expect_value(&mut args, &arg)?),
+                "--disable" => options.disabled.push(expect_value(&mut args, &arg)?),
+                "--fuel" => options.limits.fuel = Some(expect_number(&mut args, &arg)?),
+                "--timeout" => {
+                    let ms = expect_number(&mut args, &arg)?;
+                    options.limits.timeout = Some(Duration::from_millis(ms));
+                }
+                "--max-tape" => options.limits.max_tape = Some(expect_number(&mut args, &arg)?),
+                flag if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+                _ => options.path = arg,
+            }
+        }
+
+        Ok(options)
+    }
+}
+fn expect_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{flag} expects a value"))
+}
+fn expect_number<T: FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<T, String> {
+    let value = expect_value(args, flag)?;
+    value
+        .parse()
+        .map_err(|_| format!("{flag} expects a number, got {value}"))
+}
+
+fn debug(mut args: Peekable<impl Iterator<Item = String>>) -> Result<(), String> {
+    let mut input = Vec::new();
+    let mut fuel = Some(debugger::STEP_LIMIT);
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                let file = expect_value(&mut args, &arg)?;
+                input = std::fs::read(&file).map_err(|err| format!("cannot read {file}: {err}"))?;
+            }
+            "--fuel" => fuel = Some(expect_number(&mut args, &arg)?),
+            flag if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or("debug expects a file")?;
+    let src = std::fs::read_to_string(&path).map_err(|err| format!("cannot read {path}: {err}"))?;
+
+    let mut debugger = Debugger::new(lex_spanned(Cursor::new(src.as_str())), input)?;
+    debugger.set_step_limit(fuel);
+    debugger::repl::run(&mut debugger, &src, stdin().lock(), stdout()).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "serde")]
+fn print_json(value: &impl serde::Serialize) {
+    serde_json::to_writer_pretty(stdout(), value).unwrap();
+    println!();
+}
+#[cfg(not(feature = "serde"))]
+fn print_json<T>(_: &T) {
+    eprintln!("--emit-json needs rustfck to be built with the serde feature");
+    exit(2);
+}
+
+/// Formats or minifies every file, or stdin when there are none.
+fn reformat(mut args: impl Iterator<Item = String>, minifying: bool) -> Result<(), String> {
+    let mut options = FormatOptions::default();
+    let mut dialect = Dialect::default();
+    let mut write = false;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" if !minifying => options.width = expect_number(&mut args, &arg)?,
+            "--indent" if !minifying => options.indent = expect_number(&mut args, &arg)?,
+            "--strip-comments" if !minifying => options.comments = false,
+            "--debug-dump" => dialect.debug_dump = true,
+            "--input-separator" => dialect.input_separator = true,
+            "--write" => write = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+            _ => paths.push(arg),
+        }
+    }
+
+    let transform = |src: &str| match minifying {
+        true => Ok(minify(src, dialect)),
+        false => format(src, dialect, options),
+    };
+    if paths.is_empty() {
+        let mut src = String::new();
+        stdin()
+            .read_to_string(&mut src)
+            .map_err(|err| format!("cannot read stdin: {err}"))?;
+        print!("{}", transform(&src)?);
+        return Ok(());
+    }
+    for path in paths {
+        let src =
+            std::fs::read_to_string(&path).map_err(|err| format!("cannot read {path}: {err}"))?;
+        let result = transform(&src).map_err(|err| format!("{path}: {err}"))?;
+        if write {
+            std::fs::write(&path, result).map_err(|err| format!("cannot write {path}: {err}"))?;
+        } else {
+            print!("{result}");
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether none of the files had any lints.
+fn lint_files(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut dialect = Dialect::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--debug-dump" => dialect.debug_dump = true,
+            "--input-separator" => dialect.input_separator = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err("lint expects at least one file".to_owned());
+    }
+
+    let mut clean = true;
+    for path in paths {
+        let src =
+            std::fs::read_to_string(&path).map_err(|err| format!("cannot read {path}: {err}"))?;
+        for lint in lint(&src, dialect).map_err(|err| format!("{path}: {err}"))? {
+            let (line, col) = lint.span.line_col(&src);
+            println!("{path}:{line}:{col}: warning: {lint}");
+            clean = false;
+        }
+    }
+    Ok(clean)
+}
+
+/// Returns whether both traces had the same effects.
+fn trace_diff(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut end_state = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--end-state" => end_state = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+            _ => paths.push(arg),
+        }
+    }
+    let [left, right] = &paths[..] else {
+        return Err("trace-diff expects two traces".to_owned());
+    };
+    let read = |path: &String| {
+        File::open(path)
+            .and_then(TraceReader::new)
+            .map_err(|err| format!("cannot read {path}: {err}"))
+    };
+    let (left_events, right_events) = (read(left)?, read(right)?);
+
+    let divergence = if end_state {
+        let replay =
+            |events, path| Replay::new(events).map_err(|err| format!("cannot read {path}: {err}"));
+        diff_end_states(&replay(left_events, left)?, &replay(right_events, right)?)
+    } else {
+        diff(left_events, right_events).map_err(|err| format!("cannot read traces: {err}"))?
+    };
+    match divergence {
+        Some(divergence) => {
+            println!("{divergence}");
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
 
 fn main() {
-    let program_name = "mandelbrot";
+    let mut args = args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("debug") => {
+            args.next();
+            if let Err(err) = debug(args) {
+                eprintln!("{err}");
+                exit(1);
+            }
+            return;
+        }
+        Some(command @ ("fmt" | "minify")) => {
+            let minifying = command == "minify";
+            args.next();
+            if let Err(err) = reformat(args, minifying) {
+                eprintln!("{err}");
+                exit(1);
+            }
+            return;
+        }
+        Some("lint") => {
+            args.next();
+            match lint_files(args) {
+                Ok(clean) => exit(if clean { 0 } else { 1 }),
+                Err(err) => {
+                    eprintln!("{err}\n{USAGE}");
+                    exit(2);
+                }
+            }
+        }
+        Some("trace-diff") => {
+            args.next();
+            match trace_diff(args) {
+                Ok(same) => exit(if same { 0 } else { 1 }),
+                Err(err) => {
+                    eprintln!("{err}\n{USAGE}");
+                    exit(2);
+                }
+            }
+        }
+        _ => (),
+    }
+
+    let options = Options::parse(args).unwrap_or_else(|err| {
+        eprintln!("{err}\n{USAGE}");
+        exit(2);
+    });
 
-    let src = std::fs::read_to_string(format!("./programs/{}.b", program_name)).unwrap();
-    let tokens = lex(Cursor::new(src));
-    let ast = parse(tokens);
+    let src = std::fs::read_to_string(&options.path).unwrap_or_else(|err| {
+        eprintln!("cannot read {}: {err}", options.path);
+        exit(1);
+    });
+    let tokens = lex_with(Cursor::new(src), options.dialect);
+    let ast = try_parse(tokens).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", options.path);
+        exit(1);
+    });
+    if options.emit_json == Some(Stage::Ast) {
+        print_json(&ast);
+        return;
+    }
     let mut program = ast.gen_expr_tree();
+    let input: Box<dyn Read> = match ast.input() {
+        Some(data) => Box::new(Cursor::new(data.to_vec())),
+        None => Box::new(stdin()),
+    };
+
+    // O2 runs every pass there is, so its names are the ones worth accepting.
+    let mut known: Vec<_> = optimize::pass_manager(OptLevel::O2)
+        .pass_names()
+        .chain(ir::optimize::pass_manager(OptLevel::O2).pass_names())
+        .collect();
+    known.sort();
+    known.dedup();
+    if let Some(name) = (options.disabled.iter())
+        .chain(&options.dump_after)
+        .find(|name| !known.contains(&name.as_str()))
+    {
+        eprintln!("unknown pass {name}, expected one of {}", known.join(", "));
+        exit(2);
+    }
+
+    let mut frontend = optimize::limited_pass_manager(options.level, options.limits);
+    let mut backend = ir::optimize::pass_manager(options.level);
+    for name in &options.disabled {
+        frontend.disable(name);
+        backend.disable(name);
+    }
+    for name in &options.dump_after {
+        frontend.dump_after(name);
+        backend.dump_after(name);
+    }
+
+    frontend.run(&mut program);
+    if options.emit_json == Some(Stage::ExprTree) {
+        print_json(&program);
+        return;
+    }
+    if options.emit_bf {
+        let bf = emit(&program).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            exit(1);
+        });
+        println!("{bf}");
+        return;
+    }
+    if options.interpret {
+        if options.time_passes {
+            frontend.print_stats(stderr()).unwrap();
+        }
+        let mut exec = TreeExec::new(stdout(), input);
+        exec.set_limits(options.limits);
+        if let Err(err) = exec.exec_program(&program) {
+            eprintln!("{err}");
+            exit(1);
+        }
+        return;
+    }
+
+    let (mut module, loops) = gen_program_with_loops(&program);
+    backend.run(&mut module);
+
+    if options.time_passes {
+        frontend.print_stats(stderr()).unwrap();
+        backend.print_stats(stderr()).unwrap();
+    }
+    if options.emit_json == Some(Stage::Ir) {
+        print_json(&module);
+        return;
+    }
+    if options.print_ir {
+        Printer::new(stdout()).print_module(&module).unwrap();
+        return;
+    }
+    if options.print_dot {
+        DotWriter::new(stdout(), options.dot)
+            .write_module(&module)
+            .unwrap();
+        return;
+    }
+
+    let profiling = options.profile || options.profile_folded.is_some();
+    if profiling || options.trace.is_some() {
+        let mut exec = Exec::new(stdout(), input);
+        exec.set_limits(options.limits);
+        if profiling {
+            exec.enable_profiling();
+        }
+        if let Some(path) = &options.trace {
+            if let Err(err) = File::create(path).and_then(|f| exec.record_trace(f)) {
+                eprintln!("cannot write {path}: {err}");
+                exit(1);
+            }
+        }
+        let result = exec.exec_program(&module);
 
-    apply_optimizations(&mut program);
+        if let (true, Some(profile)) = (options.profile, exec.profile()) {
+            write_report(stderr(), &loops, profile).unwrap();
+        }
+        if let (Some(path), Some(profile)) = (&options.profile_folded, exec.profile()) {
+            let written = File::create(path).and_then(|f| write_folded(f, &loops, profile));
+            if let Err(err) = written {
+                eprintln!("cannot write {path}: {err}");
+                exit(1);
+            }
+        }
+        // Flushes the trace, which a failed run would otherwise lose on exit.
+        drop(exec);
+        if let Err(err) = result {
+            eprintln!("{err}");
+            exit(1);
+        }
+        return;
+    }
 
-    let mut module = gen_program(&program);
-    optimize_module(&mut module);
-    Printer::new(stdout()).print_module(&module).unwrap();
-    let mut exec = Exec::new(stdout(), stdin());
-    exec.exec_program(&module).unwrap();
+    let code = lower(&module).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        exit(1);
+    });
+    let mut vm = Vm::new(stdout(), input);
+    vm.set_limits(options.limits);
+    if let Err(err) = vm.exec(&code) {
+        eprintln!("{err}");
+        exit(1);
+    }
 }
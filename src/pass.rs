@@ -0,0 +1,205 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    io::{self, stderr, Write},
+    time::{Duration, Instant},
+};
+
+pub trait PassTarget: Clone + PartialEq {
+    fn instruction_count(&self) -> usize;
+    fn dump(&self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+}
+impl OptLevel {
+    pub fn parse(flag: &str) -> Option<Self> {
+        match flag {
+            "-O0" => Some(Self::O0),
+            "-O1" => Some(Self::O1),
+            "-O2" => Some(Self::O2),
+            _ => None,
+        }
+    }
+}
+impl Display for OptLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::O0 => write!(f, "-O0"),
+            Self::O1 => write!(f, "-O1"),
+            Self::O2 => write!(f, "-O2"),
+        }
+    }
+}
+
+pub struct Pass<T> {
+    name: &'static str,
+    run: Box<dyn Fn(&mut T) -> bool>,
+}
+
+pub struct PassManager<T> {
+    passes: Vec<Pass<T>>,
+    max_iterations: usize,
+    disabled: HashSet<String>,
+    dump_after: HashSet<String>,
+    stats: Vec<PassStats>,
+    iterations: usize,
+    converged: bool,
+}
+impl<T: PassTarget> PassManager<T> {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            max_iterations: 1,
+            disabled: HashSet::new(),
+            dump_after: HashSet::new(),
+            stats: Vec::new(),
+            iterations: 0,
+            converged: false,
+        }
+    }
+
+    pub fn add_pass(&mut self, name: &'static str, run: impl Fn(&mut T) -> bool + 'static) {
+        self.passes.push(Pass {
+            name,
+            run: Box::new(run),
+        });
+    }
+    /// Runs `run` wherever the pass called `name` would run.
+    pub fn replace_pass(&mut self, name: &str, run: impl Fn(&mut T) -> bool + Clone + 'static) {
+        for pass in self.passes.iter_mut().filter(|p| p.name == name) {
+            pass.run = Box::new(run.clone());
+        }
+    }
+    pub fn set_max_iterations(&mut self, max: usize) {
+        self.max_iterations = max;
+    }
+    pub fn disable(&mut self, name: &str) {
+        self.disabled.insert(name.to_owned());
+    }
+    pub fn dump_after(&mut self, name: &str) {
+        self.dump_after.insert(name.to_owned());
+    }
+
+    pub fn pass_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().map(|p| p.name)
+    }
+    pub fn stats(&self) -> &[PassStats] {
+        &self.stats
+    }
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    pub fn run(&mut self, target: &mut T) -> bool {
+        let mut changed_any = false;
+        self.iterations = 0;
+        self.converged = false;
+
+        while self.iterations < self.max_iterations {
+            self.iterations += 1;
+            let mut changed = false;
+
+            for pass in &self.passes {
+                if self.disabled.contains(pass.name) {
+                    continue;
+                }
+
+                #[cfg(debug_assertions)]
+                let snapshot = target.clone();
+
+                let before = target.instruction_count();
+                let start = Instant::now();
+                let pass_changed = (pass.run)(target);
+                let time = start.elapsed();
+                let after = target.instruction_count();
+                changed |= pass_changed;
+
+                #[cfg(debug_assertions)]
+                assert!(
+                    pass_changed || *target == snapshot,
+                    "pass {} reported no change but modified its input",
+                    pass.name
+                );
+
+                let stats = stats_for(&mut self.stats, pass.name);
+                stats.runs += 1;
+                stats.changes += pass_changed as usize;
+                stats.time += time;
+                stats.removed += before.saturating_sub(after);
+                stats.added += after.saturating_sub(before);
+
+                if self.dump_after.contains(pass.name) {
+                    let mut err = stderr().lock();
+                    let _ = writeln!(err, "; after {} (iteration {})", pass.name, self.iterations);
+                    let _ = target.dump(&mut err);
+                }
+            }
+
+            changed_any |= changed;
+            if !changed {
+                self.converged = true;
+                break;
+            }
+        }
+
+        changed_any
+    }
+
+    pub fn print_stats<O: Write>(&self, mut out: O) -> io::Result<()> {
+        writeln!(
+            out,
+            "{:<28} {:>6} {:>8} {:>10} {:>8} {:>8}",
+            "pass", "runs", "changes", "time", "removed", "added"
+        )?;
+        for s in &self.stats {
+            writeln!(
+                out,
+                "{:<28} {:>6} {:>8} {:>10.3?} {:>8} {:>8}",
+                s.name, s.runs, s.changes, s.time, s.removed, s.added
+            )?;
+        }
+        let total: Duration = self.stats.iter().map(|s| s.time).sum();
+        writeln!(out, "{} iteration(s), {total:.3?} total", self.iterations)
+    }
+}
+impl<T: PassTarget> Default for PassManager<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PassStats {
+    pub name: &'static str,
+    pub runs: usize,
+    pub changes: usize,
+    pub time: Duration,
+    pub removed: usize,
+    pub added: usize,
+}
+
+fn stats_for<'a>(stats: &'a mut Vec<PassStats>, name: &'static str) -> &'a mut PassStats {
+    let index = match stats.iter().position(|s| s.name == name) {
+        Some(index) => index,
+        None => {
+            stats.push(PassStats {
+                name,
+                runs: 0,
+                changes: 0,
+                time: Duration::ZERO,
+                removed: 0,
+                added: 0,
+            });
+            stats.len() - 1
+        }
+    };
+    &mut stats[index]
+}
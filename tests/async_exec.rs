@@ -0,0 +1,79 @@
+#![cfg(feature = "async")]
+
+use rustfck::{
+    ir::{
+        exec::Exec,
+        limits::{ExecError, Limits},
+    },
+    pass::OptLevel,
+};
+use std::cell::Cell;
+use tokio::io::{duplex, empty, sink, AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::compile_module;
+
+#[tokio::test]
+async fn echoes_over_a_stream_without_blocking() {
+    let module = compile_module(",[.[-],]", OptLevel::O2);
+    let (mut client, server) = duplex(4);
+    let (server_in, server_out) = tokio::io::split(server);
+
+    let run = async {
+        let mut exec = Exec::new(server_out, server_in);
+        exec.exec_program_async(&module).await
+    };
+    let talk = async {
+        client.write_all(b"hel").await.unwrap();
+        tokio::task::yield_now().await;
+        client.write_all(b"lo").await.unwrap();
+        let mut echoed = [0; 5];
+        client.read_exact(&mut echoed).await.unwrap();
+        client.shutdown().await.unwrap();
+        echoed
+    };
+
+    let (result, echoed) = tokio::join!(run, talk);
+    result.unwrap();
+    assert_eq!(&echoed, b"hello");
+}
+
+#[tokio::test]
+async fn long_computations_yield_to_other_tasks() {
+    let module = compile_module("+[>+<]", OptLevel::O2);
+    let mut exec = Exec::new(sink(), empty());
+    exec.set_limits(Limits {
+        fuel: Some(200_000),
+        ..Limits::default()
+    });
+
+    let ticked = Cell::new(false);
+    let run = async {
+        let result = exec.exec_program_async(&module).await;
+        (result, ticked.get())
+    };
+    let ticker = async {
+        tokio::task::yield_now().await;
+        ticked.set(true);
+    };
+    let ((result, ticked_before_finishing), ()) = tokio::join!(run, ticker);
+
+    assert!(matches!(result, Err(ExecError::OutOfFuel(_))), "{result:?}");
+    assert!(ticked_before_finishing);
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[tokio::test]
+async fn runs_on_a_spawned_task_while_tracing() {
+    let module = compile_module("+++.", OptLevel::O2);
+    let mut exec = Exec::new(sink(), empty());
+    exec.record_trace(std::io::sink()).unwrap();
+    assert_send(&exec.exec_program_async(&module));
+
+    let cells = tokio::spawn(async move {
+        exec.exec_program_async(&module).await.unwrap();
+        exec.cells().to_vec()
+    });
+    assert_eq!(cells.await.unwrap(), [3]);
+}
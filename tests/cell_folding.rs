@@ -0,0 +1,88 @@
+use rustfck::frontend::{
+    expr_tree::{BoundsRange, Instruction, Program},
+    optimize::{apply_optimizations, fold_known_cells},
+};
+
+mod common;
+use common::{compile, run};
+
+fn checked(mut body: Vec<Instruction>) -> Program {
+    body.insert(
+        0,
+        Instruction::BoundsCheck(BoundsRange {
+            start: 0,
+            length: 4,
+        }),
+    );
+    Program(body)
+}
+
+#[test]
+fn set_then_modify_becomes_set() {
+    use Instruction::*;
+    let mut program = checked(vec![Set(1, 0), Modify(1, 5), Modify(1, -2), Output(1)]);
+    fold_known_cells(&mut program);
+    assert_eq!(program, checked(vec![Set(1, 3), Output(1)]));
+}
+
+#[test]
+fn set_then_add_becomes_copy() {
+    use Instruction::*;
+    let add = AddMultiple {
+        target: 1,
+        base: 0,
+        factor: 3,
+    };
+    let copy = Copy {
+        target: 1,
+        base: 0,
+        factor: 3,
+    };
+    let mut program = checked(vec![Input(0), Set(1, 0), add, Output(1)]);
+    fold_known_cells(&mut program);
+    assert_eq!(program, checked(vec![Input(0), copy, Output(1)]));
+}
+
+#[test]
+fn redundant_sets_are_removed() {
+    use Instruction::*;
+    let mut program = checked(vec![
+        Set(1, 4),
+        Set(2, 0),
+        Set(1, 4),
+        Output(1),
+        Set(1, 7),
+        Set(1, 4),
+        Output(1),
+    ]);
+    fold_known_cells(&mut program);
+    assert_eq!(
+        program,
+        checked(vec![Set(1, 4), Set(2, 0), Output(1), Output(1)])
+    );
+}
+
+#[test]
+fn folding_preserves_output() {
+    let sources = [
+        ",++>+++++[-]+++<[->>[-]<+<]>>[-<+>]+++.<.<.",
+        ",>++++++++[<+++++++++>-]<.>++++[<+++++++>-]<+.+++++++..+++.",
+        ",+++[>[-]++<-]>.[-]>[-]<[>+<-]>+.",
+    ];
+    for src in sources {
+        let reference = compile(src);
+        let mut optimized = reference.clone();
+        apply_optimizations(&mut optimized);
+        assert_eq!(run(&optimized, &[]), run(&reference, &[]), "{src}");
+    }
+}
+
+#[test]
+fn overwritten_input_is_not_assumed_known() {
+    let src = ",[-]><+[-].";
+    let reference = compile(src);
+    let mut optimized = reference.clone();
+    apply_optimizations(&mut optimized);
+    assert_eq!(run(&optimized, &[154]), run(&reference, &[154]));
+    assert_eq!(run(&optimized, &[154]), [0]);
+}
@@ -0,0 +1,73 @@
+//! Helpers shared by the integration tests. Every test crate uses a different
+//! subset of them.
+#![allow(dead_code)]
+
+use rustfck::{
+    frontend::{
+        code_gen::gen_program,
+        expr_tree::{Instruction, Program},
+        lexer::lex,
+        optimize,
+        parser::parse,
+    },
+    ir::{self, bytecode::lower, exec::Exec, vm::Vm, Module},
+    pass::OptLevel,
+};
+use std::io::{empty, Cursor};
+
+/// Parses `src` into an unoptimized expression tree.
+pub fn compile(src: &str) -> Program {
+    parse(lex(Cursor::new(src))).gen_expr_tree()
+}
+/// Like [`compile`], but runs the frontend passes of `level` as well.
+pub fn optimized(src: &str, level: OptLevel) -> Program {
+    let mut program = compile(src);
+    optimize::pass_manager(level).run(&mut program);
+    program
+}
+/// Compiles `src` to a module, running the frontend and IR passes of `level`.
+pub fn compile_module(src: &str, level: OptLevel) -> Module {
+    let mut module = gen_program(&optimized(src, level));
+    ir::optimize::pass_manager(level).run(&mut module);
+    module
+}
+
+/// Runs `program` on [`Exec`] and returns what it printed.
+pub fn run(program: &Program, input: &[u8]) -> Vec<u8> {
+    run_module(&gen_program(program), input)
+}
+pub fn run_module(module: &Module, input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    Exec::new(&mut out, input).exec_program(module).unwrap();
+    out
+}
+/// Runs `module` on [`Exec`] and the [`Vm`], checking that both print the
+/// same, and returns what they printed.
+pub fn run_both(module: &Module) -> Vec<u8> {
+    let mut expected = Vec::new();
+    Exec::new(&mut expected, empty())
+        .exec_program(module)
+        .unwrap();
+
+    let mut output = Vec::new();
+    Vm::new(&mut output, empty())
+        .exec(&lower(module).unwrap())
+        .unwrap();
+    assert_eq!(output, expected);
+    output
+}
+
+/// Drops the zero cells at the end of `tape`, which engines that grow the tape
+/// differently disagree on.
+pub fn trimmed(tape: &[u8]) -> &[u8] {
+    let len = tape.iter().rposition(|&c| c != 0).map_or(0, |i| i + 1);
+    &tape[..len]
+}
+
+pub fn contains_loop(i: &[Instruction]) -> bool {
+    i.iter().any(|i| match i {
+        Instruction::Loop(..) => true,
+        Instruction::If(_, _, body) => contains_loop(body),
+        _ => false,
+    })
+}
@@ -0,0 +1,64 @@
+use rustfck::{
+    frontend::{code_gen::gen_program, optimize},
+    ir::optimize::pass_manager,
+    pass::OptLevel,
+};
+
+mod common;
+use common::{compile, run_module};
+
+const PROGRAMS: &[(&str, &[u8])] = &[
+    (
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+        b"",
+    ),
+    (",[.,]", b"echo this back\0"),
+    (">,[>,]<[.<]", b"reversed\0"),
+    (
+        ",>,<[>[->+>+<<]>>[-<<+>>]<<<-]>>>++++++[<++++++++>-]<.",
+        b"\x03\x02",
+    ),
+    (
+        ",[>>++++++++[<++++++>-]<[-<->]<[>+>+<<-]>[<+>-]>[>+<-]<<,]>>>.",
+        b"1234\0",
+    ),
+    (",[->+>++<<]>[->+<]>.", b"\x05"),
+];
+
+#[test]
+fn ir_pipeline_reaches_fixed_point() {
+    for &(src, input) in PROGRAMS {
+        for level in [OptLevel::O1, OptLevel::O2] {
+            let mut program = compile(src);
+            optimize::pass_manager(level).run(&mut program);
+            let mut module = gen_program(&program);
+
+            let mut manager = pass_manager(OptLevel::O2);
+            manager.run(&mut module);
+            assert!(manager.converged(), "{src} did not converge at {level}");
+
+            let settled = module.clone();
+            assert!(!manager.run(&mut module), "{src} changed after converging");
+            assert_eq!(module, settled);
+            assert_eq!(manager.iterations(), 1);
+
+            let reference = gen_program(&compile(src));
+            assert_eq!(
+                run_module(&module, input),
+                run_module(&reference, input),
+                "{src}"
+            );
+        }
+    }
+}
+
+#[test]
+fn frontend_pipeline_is_idempotent() {
+    for &(src, _) in PROGRAMS {
+        let mut program = compile(src);
+        optimize::pass_manager(OptLevel::O2).run(&mut program);
+        let settled = program.clone();
+        optimize::pass_manager(OptLevel::O2).run(&mut program);
+        assert_eq!(program, settled, "{src}");
+    }
+}
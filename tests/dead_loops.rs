@@ -0,0 +1,50 @@
+use rustfck::frontend::{
+    expr_tree::{Instruction, Program},
+    optimize::{mark_balanced_blocks, normalize_pointer_movement, remove_dead, remove_dead_loops},
+};
+
+mod common;
+use common::compile;
+
+fn without_dead_loops(src: &str) -> Program {
+    let mut program = compile(src);
+    normalize_pointer_movement(&mut program);
+    remove_dead(&mut program);
+    mark_balanced_blocks(&mut program);
+    remove_dead_loops(&mut program);
+    program
+}
+
+fn count_blocks(i: &[Instruction]) -> usize {
+    i.iter()
+        .map(|i| match i {
+            Instruction::Loop(_, _, body) | Instruction::If(_, _, body) => 1 + count_blocks(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[test]
+fn leading_clear_is_removed() {
+    let program = without_dead_loops("[-]+.");
+    assert!(!program.0.contains(&Instruction::Set(0, 0)));
+}
+
+#[test]
+fn loops_after_moves_are_removed() {
+    assert_eq!(count_blocks(&without_dead_loops(">>[->+<]<[.]+.").0), 0);
+}
+
+#[test]
+fn loops_after_known_zero_cells_are_removed() {
+    assert_eq!(
+        count_blocks(&without_dead_loops(",[>+<-]>[-]<[.-]>[,.]").0),
+        1
+    );
+}
+
+#[test]
+fn possibly_nonzero_loops_are_kept() {
+    assert_eq!(count_blocks(&without_dead_loops(",[.]>,[>]<[.-]").0), 3);
+    assert_eq!(count_blocks(&without_dead_loops("+[[-]>[.]+<]").0), 2);
+}
@@ -0,0 +1,150 @@
+use rustfck::frontend::{
+    debugger::{repl, Debugger, Location, Stop},
+    lexer::{lex_spanned, Span},
+};
+use std::io::Cursor;
+
+fn debugger(src: &str, input: &[u8]) -> Debugger {
+    Debugger::new(lex_spanned(Cursor::new(src)), input.to_vec()).unwrap()
+}
+
+fn at(debugger: &Debugger) -> usize {
+    debugger.location().span().unwrap().start
+}
+
+#[test]
+fn breakpoints_stop_before_the_instruction() {
+    let mut d = debugger("++[->+<]\n>.", b"");
+    assert!(d.add_breakpoint(9));
+    assert!(!d.add_breakpoint(8), "newlines are not instructions");
+
+    assert_eq!(d.resume(), Stop::Breakpoint(9));
+    assert_eq!(d.pointer(), 0);
+    assert_eq!(d.cell(1), 2);
+    assert_eq!(d.resume(), Stop::Halted);
+    assert_eq!(d.location(), Location::Halted);
+    assert_eq!(d.output(), [2]);
+}
+
+#[test]
+fn breakpoints_inside_a_run_stop_at_its_start() {
+    let mut d = debugger(">+++.", b"");
+    assert!(d.add_breakpoint(3));
+    assert_eq!(d.resume(), Stop::Breakpoint(3));
+    assert_eq!(
+        d.location(),
+        Location::Instruction(Span { start: 1, end: 4 })
+    );
+}
+
+#[test]
+fn step_enters_loops_and_next_runs_them() {
+    let mut d = debugger("+[-]+", b"");
+    d.step();
+    assert_eq!(at(&d), 1);
+    d.step();
+    assert_eq!(at(&d), 2, "step enters the loop");
+    d.step();
+    assert_eq!(d.location(), Location::LoopEnd(Span::at(3)));
+    d.step();
+    assert_eq!(at(&d), 4);
+
+    let mut d = debugger("+[-]+", b"");
+    d.step();
+    assert_eq!(d.step_over(), Stop::Done);
+    assert_eq!(at(&d), 4, "next runs the whole loop");
+    assert_eq!(d.cell(0), 0);
+}
+
+#[test]
+fn finish_leaves_the_current_loop() {
+    let mut d = debugger("+++[>+<-]>.", b"");
+    d.step();
+    d.step();
+    d.step();
+    assert_eq!(at(&d), 5);
+    assert_eq!(d.finish(), Stop::Done);
+    assert_eq!(at(&d), 9);
+    assert_eq!(d.cell(1), 3);
+}
+
+#[test]
+fn watchpoints_report_changes() {
+    let mut d = debugger(",>++<[-]", b"A");
+    d.watch(1);
+    assert_eq!(
+        d.resume(),
+        Stop::Watchpoint {
+            cell: 1,
+            old: 0,
+            new: 2
+        }
+    );
+    d.unwatch(1);
+    d.watch(0);
+    assert_eq!(
+        d.resume(),
+        Stop::Watchpoint {
+            cell: 0,
+            old: 65,
+            new: 64
+        }
+    );
+}
+
+#[test]
+fn tape_view_and_pending_output() {
+    let mut d = debugger("+.>++.>>+++", b"");
+    d.resume();
+    assert_eq!(d.tape_around_pointer(2), (1, vec![2, 0, 3, 0, 0]));
+    assert_eq!(d.take_pending_output(), [1, 2]);
+    assert!(d.take_pending_output().is_empty());
+}
+
+#[test]
+fn moving_left_of_the_tape_stops() {
+    let mut d = debugger("<+", b"");
+    assert_eq!(d.resume(), Stop::NegativePointer);
+}
+
+#[test]
+fn unmatched_brackets_are_rejected() {
+    assert!(Debugger::new(lex_spanned(Cursor::new("+[")), Vec::new()).is_err());
+    assert!(Debugger::new(lex_spanned(Cursor::new("+]")), Vec::new()).is_err());
+}
+
+#[test]
+fn scripted_session() {
+    let src = "++[->+<]\n>.";
+    let mut d = debugger(src, b"");
+    let mut out = Vec::new();
+    repl::run(&mut d, src, &b"b 2:2\nc\nt 1\no\nc\no\n"[..], &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(
+        out.contains("breakpoint at 2:2\n2:2 | >.\n    |  ^\n"),
+        "{out}"
+    );
+    assert!(out.contains("0: 0  [1: 2]  2: 0"), "{out}");
+    assert!(out.contains("no pending output"), "{out}");
+    assert!(out.contains("program finished after"), "{out}");
+    assert!(out.contains("\"\\x02\""), "{out}");
+}
+
+#[test]
+fn infinite_loops_hand_control_back() {
+    let mut d = debugger("+[]", b"");
+    d.set_step_limit(Some(100));
+    assert_eq!(d.resume(), Stop::StepLimit);
+    assert_eq!(d.steps(), 100);
+    assert_eq!(d.resume_for(5), Stop::StepLimit);
+    assert_eq!(d.steps(), 105);
+
+    let src = "+[]";
+    let mut d = debugger(src, b"");
+    let mut out = Vec::new();
+    repl::run(&mut d, src, &b"c 10\nc x\n"[..], &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("paused after 10 steps"), "{out}");
+    assert!(out.contains("continue expects a number of steps"), "{out}");
+}
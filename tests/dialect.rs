@@ -0,0 +1,86 @@
+use rustfck::{
+    frontend::{
+        code_gen::gen_program,
+        expr_tree::Instruction,
+        lexer::{lex, lex_with, Dialect, Token},
+        optimize::apply_optimizations,
+        parser::{parse, try_parse},
+    },
+    ir::{exec::dump_tape, exec::Exec, optimize::optimize_module},
+};
+use std::io::Cursor;
+
+const ALL: Dialect = Dialect {
+    debug_dump: true,
+    input_separator: true,
+};
+
+#[test]
+fn extensions_are_comments_by_default() {
+    let tokens: Vec<_> = lex(Cursor::new("+#!-")).collect();
+    assert_eq!(tokens, [Token::Plus, Token::Minus]);
+}
+
+#[test]
+fn dialect_tokens() {
+    let tokens: Vec<_> = lex_with(Cursor::new("+#!-x"), ALL).collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Plus,
+            Token::Dump,
+            Token::EndOfCode,
+            Token::Data(b'-'),
+            Token::Data(b'x'),
+        ]
+    );
+}
+
+#[test]
+fn input_follows_the_separator() {
+    let ast = parse(lex_with(Cursor::new(",[.[-],]!hi\n"), ALL));
+    assert_eq!(ast.input(), Some(&b"hi\n"[..]));
+
+    let mut module = gen_program(&ast.gen_expr_tree());
+    optimize_module(&mut module);
+    let mut output = Vec::new();
+    Exec::new(&mut output, ast.input().unwrap())
+        .exec_program(&module)
+        .unwrap();
+    assert_eq!(output, b"hi\n");
+
+    assert_eq!(parse(lex_with(Cursor::new(",."), ALL)).input(), None);
+}
+
+#[test]
+fn dumps_show_the_cells_around_the_pointer() {
+    let mut out = Vec::new();
+    dump_tape(&mut out, &[1, 2, 3], 1).unwrap();
+    assert_eq!(out, b"cells 0..10, pointer 1: 1 [2] 3 0 0 0 0 0 0 0\n");
+
+    let mut out = Vec::new();
+    dump_tape(&mut out, &[0; 20], 12).unwrap();
+    assert_eq!(
+        out,
+        b"cells 4..21, pointer 12: 0 0 0 0 0 0 0 0 [0] 0 0 0 0 0 0 0 0\n"
+    );
+}
+
+#[test]
+fn dumps_observe_stores_the_optimizer_would_drop() {
+    let mut program = parse(lex_with(Cursor::new("++#[-]>+<"), ALL)).gen_expr_tree();
+    apply_optimizations(&mut program);
+
+    let dump = program.0.iter().position(|i| *i == Instruction::Dump(0));
+    let set = program.0.iter().position(|i| *i == Instruction::Set(0, 2));
+    assert!(set.unwrap() < dump.unwrap(), "{program:?}");
+}
+
+#[test]
+fn unmatched_brackets_are_errors() {
+    let parse = |src| try_parse(lex_with(Cursor::new(src), ALL));
+    assert_eq!(parse("+[!abc]"), Err("unmatched [".to_string()));
+    assert_eq!(parse("+[-"), Err("unmatched [".to_string()));
+    assert_eq!(parse("+]"), Err("unmatched ]".to_string()));
+    assert_eq!(parse("+!]").unwrap().input(), Some(&b"]"[..]));
+}
@@ -0,0 +1,136 @@
+//! Runs every program in `tests/programs` through each pipeline configuration.
+//!
+//! A case is a `<name>.b` source file, an optional `<name>.in` with its stdin
+//! and a `<name>.out` with the expected stdout. Every configuration must
+//! produce the expected output and leave the same tape as the unoptimized run.
+
+use rustfck::{
+    frontend::{code_gen::gen_program, exec::TreeExec, expr_tree::Program, optimize},
+    ir::{self, bytecode::lower, exec::Exec, vm::Vm},
+    pass::OptLevel,
+};
+use std::{fs, path::Path};
+
+mod common;
+use common::{compile, trimmed};
+
+struct Case {
+    name: String,
+    program: Program,
+    input: Vec<u8>,
+    expected: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Run {
+    output: Vec<u8>,
+    tape: Vec<u8>,
+}
+
+type Configuration = (&'static str, fn(Program, &[u8]) -> Run);
+
+const CONFIGURATIONS: &[Configuration] = &[
+    ("unoptimized", |p, input| {
+        exec(p, OptLevel::O0, OptLevel::O0, input)
+    }),
+    ("frontend-o1", |p, input| {
+        exec(p, OptLevel::O1, OptLevel::O0, input)
+    }),
+    ("frontend-o2", |p, input| {
+        exec(p, OptLevel::O2, OptLevel::O0, input)
+    }),
+    ("o1", |p, input| exec(p, OptLevel::O1, OptLevel::O1, input)),
+    ("o2", |p, input| exec(p, OptLevel::O2, OptLevel::O2, input)),
+    ("vm", |p, input| exec_vm(p, OptLevel::O0, input)),
+    ("vm-o2", |p, input| exec_vm(p, OptLevel::O2, input)),
+    ("tree", |p, input| exec_tree(p, OptLevel::O0, input)),
+    ("tree-o2", |p, input| exec_tree(p, OptLevel::O2, input)),
+];
+
+fn exec(mut program: Program, frontend: OptLevel, backend: OptLevel, input: &[u8]) -> Run {
+    optimize::pass_manager(frontend).run(&mut program);
+    let mut module = gen_program(&program);
+    ir::optimize::pass_manager(backend).run(&mut module);
+
+    let mut output = Vec::new();
+    let mut exec = Exec::new(&mut output, input);
+    exec.exec_program(&module).unwrap();
+    let tape = trimmed(exec.cells()).to_vec();
+    Run { output, tape }
+}
+
+fn exec_vm(mut program: Program, level: OptLevel, input: &[u8]) -> Run {
+    optimize::pass_manager(level).run(&mut program);
+    let mut module = gen_program(&program);
+    ir::optimize::pass_manager(level).run(&mut module);
+
+    let mut output = Vec::new();
+    let mut vm = Vm::new(&mut output, input);
+    vm.exec(&lower(&module).unwrap()).unwrap();
+    let tape = trimmed(vm.cells()).to_vec();
+    Run { output, tape }
+}
+
+fn exec_tree(mut program: Program, level: OptLevel, input: &[u8]) -> Run {
+    optimize::pass_manager(level).run(&mut program);
+
+    let mut output = Vec::new();
+    let mut exec = TreeExec::new(&mut output, input);
+    exec.exec_program(&program).unwrap();
+    let tape = trimmed(exec.cells()).to_vec();
+    Run { output, tape }
+}
+
+fn corpus() -> Vec<Case> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut cases: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "b"))
+        .map(|path| {
+            let src = fs::read_to_string(&path).unwrap();
+            let input = fs::read(path.with_extension("in")).unwrap_or_default();
+            let expected = fs::read(path.with_extension("out"))
+                .unwrap_or_else(|_| panic!("{} has no .out file", path.display()));
+            Case {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                program: compile(&src),
+                input,
+                expected,
+            }
+        })
+        .collect();
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
+
+#[test]
+fn all_configurations_agree() {
+    let cases = corpus();
+    assert!(!cases.is_empty());
+
+    for case in cases {
+        let (_, reference) = CONFIGURATIONS[0];
+        let reference = reference(case.program.clone(), &case.input);
+        assert_eq!(
+            reference.output, case.expected,
+            "{}: unoptimized output differs from the fixture",
+            case.name
+        );
+
+        for &(config, run) in &CONFIGURATIONS[1..] {
+            let result = run(case.program.clone(), &case.input);
+            assert_eq!(
+                String::from_utf8_lossy(&result.output),
+                String::from_utf8_lossy(&case.expected),
+                "{}: output differs under {config}",
+                case.name
+            );
+            assert_eq!(
+                result.tape, reference.tape,
+                "{}: tape differs under {config}",
+                case.name
+            );
+        }
+    }
+}
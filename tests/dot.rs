@@ -0,0 +1,100 @@
+use rustfck::{
+    ir::{
+        block::BlockID,
+        cfg::Cfg,
+        dot::{DotOptions, DotWriter},
+        Module,
+    },
+    pass::OptLevel,
+};
+
+mod common;
+use common::compile_module;
+
+fn blocks(ids: &[usize]) -> Vec<BlockID> {
+    ids.iter().map(|&id| BlockID::from(id)).collect()
+}
+
+fn dot(module: &Module, options: DotOptions) -> String {
+    let mut out = Vec::new();
+    DotWriter::new(&mut out, options)
+        .write_module(module)
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn dominators_of_nested_loops() {
+    let cfg = Cfg::new(&compile_module("+[>+[>+<-]<-]", OptLevel::O0));
+    let idom = |b: usize| cfg.immediate_dominator(BlockID::from(b));
+
+    assert_eq!(idom(0), None);
+    assert_eq!(idom(1), Some(BlockID::from(0)));
+    assert_eq!(idom(3), Some(BlockID::from(1)));
+    assert_eq!(idom(5), Some(BlockID::from(4)));
+    assert_eq!(idom(6), Some(BlockID::from(4)));
+    assert!(cfg.dominates(BlockID::from(1), BlockID::from(6)));
+    assert!(!cfg.dominates(BlockID::from(6), BlockID::from(3)));
+    assert_eq!(cfg.predecessors(BlockID::from(1)), blocks(&[0, 6]));
+}
+
+#[test]
+fn natural_loops() {
+    let cfg = Cfg::new(&compile_module("+[>+[>+<-]<-]", OptLevel::O0));
+    assert_eq!(
+        cfg.loops(),
+        vec![
+            (BlockID::from(1), blocks(&[1, 2, 4, 5, 6])),
+            (BlockID::from(4), blocks(&[4, 5])),
+        ]
+    );
+    let mut back_edges: Vec<_> = cfg.back_edges().collect();
+    back_edges.sort_by_key(|&(from, _)| usize::from(from));
+    assert_eq!(
+        back_edges,
+        vec![
+            (BlockID::from(5), BlockID::from(4)),
+            (BlockID::from(6), BlockID::from(1)),
+        ]
+    );
+}
+
+#[test]
+fn edges_show_polarity_and_arguments() {
+    let out = dot(&compile_module("+[>]", OptLevel::O0), DotOptions::default());
+
+    assert!(out.starts_with("digraph module {\n"));
+    assert!(out.contains("\t0 [label=\"@0:\\l"));
+    assert!(out.contains("\t1 -> 2 [label=\"true\"];"));
+    assert!(out.contains("\t1 -> 3 [label=\"false (%8)\", color=red];"));
+    assert!(out.contains("\t2 -> 1 [label=\"(%12)\"];"));
+    assert!(!out.contains("style=dashed"));
+    assert!(!out.contains("fillcolor"));
+}
+
+#[test]
+fn highlights_loops_and_dominators() {
+    let module = compile_module("+[>+[>+<-]<-]", OptLevel::O0);
+    let out = dot(
+        &module,
+        DotOptions {
+            loops: true,
+            dominators: true,
+        },
+    );
+
+    let node = |id: usize| {
+        out.lines()
+            .find(|l| l.starts_with(&format!("\t{id} [")))
+            .unwrap()
+    };
+    assert!(node(1).contains("penwidth=2"));
+    assert!(node(4).contains("penwidth=2"));
+    assert!(!node(3).contains("fillcolor"));
+    assert_ne!(
+        node(2).split("fillcolor").nth(1),
+        node(5).split("fillcolor").nth(1)
+    );
+    assert!(out.contains("\t6 -> 1 [label=\"(%39)\", style=dashed];"));
+    assert!(out.contains("\t4 -> 6 [style=dotted"));
+}
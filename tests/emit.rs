@@ -0,0 +1,193 @@
+use rustfck::frontend::{
+    emit::{emit, EmitError},
+    exec::TreeExec,
+    expr_tree::{BoundsRange, Instruction, Program},
+    lexer::lex,
+    parser::parse,
+    reference::interpret,
+};
+use rustfck::pass::OptLevel;
+use std::{fs, io::Cursor, path::Path};
+
+mod common;
+use common::{optimized, trimmed};
+
+const FUEL: usize = 100_000;
+/// Temporaries make the regenerated source slower than the original.
+const EMITTED_FUEL: usize = 50 * FUEL;
+
+/// Runs the original through the reference interpreter and the regenerated
+/// source the same way, returning the regenerated source.
+fn check(src: &str, input: &[u8], level: OptLevel) -> String {
+    let expected = interpret(&parse(lex(Cursor::new(src))), input, FUEL).unwrap();
+    let emitted = emit(&optimized(src, level)).unwrap();
+    let actual = interpret(
+        &parse(lex(Cursor::new(emitted.as_str()))),
+        input,
+        EMITTED_FUEL,
+    )
+    .unwrap_or_else(|trap| panic!("{src} became {emitted}, which trapped with {trap:?}"));
+    assert_eq!(actual.output, expected.output, "{src} became {emitted}");
+    let tape = Some(&actual.tape)
+        .filter(|&tape| tape == &expected.tape)
+        .cloned()
+        .or_else(|| unspread(&actual.tape));
+    assert_eq!(tape, Some(expected.tape), "{src} became {emitted}");
+    emitted
+}
+
+/// Undoes the layout that gives every cell a scratch cell, whose scratch
+/// cells must all have been left zero.
+fn unspread(tape: &[u8]) -> Option<Vec<u8>> {
+    if tape.iter().skip(1).step_by(2).any(|&c| c != 0) {
+        return None;
+    }
+    let cells: Vec<u8> = tape.iter().step_by(2).copied().collect();
+    Some(trimmed(&cells).to_vec())
+}
+
+#[test]
+fn regenerates_minimal_source() {
+    assert_eq!(check("+++[->++<]>.", b"", OptLevel::O0), "+++[->++<]>.");
+    assert_eq!(
+        check(">,[->+>--<<]>>.", b"\x03", OptLevel::O1),
+        ">,[->+>--<<]>>."
+    );
+    assert_eq!(check(",[-]+++.", b"a", OptLevel::O1), ",[-]+++.");
+    assert_eq!(check("+-<>[+]++ comment .", b"", OptLevel::O1), "++.");
+}
+
+#[test]
+fn preserves_test_programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut sources: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "b"))
+        .collect();
+    sources.sort();
+
+    for path in sources {
+        let src = fs::read_to_string(&path).unwrap();
+        let input = fs::read(path.with_extension("in")).unwrap_or_default();
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            check(&src, &input, level);
+        }
+    }
+}
+
+#[test]
+fn preserves_random_programs() {
+    const PIECES: &[&str] = &[
+        "+",
+        "++",
+        "-",
+        ">",
+        "<",
+        ".",
+        ",",
+        "[-]",
+        "[->+<]",
+        "[->>+<<]",
+        "[->++>+++<<]",
+        "[>+<--]",
+        "[-]>[-<+>]<",
+        "[->+>+<<]>>[-<<+>>]<<",
+        "[>]",
+        "[<]",
+    ];
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize % n
+    };
+
+    let mut checked = 0;
+    while checked < 300 {
+        let mut src = ">>>".to_owned();
+        let mut open = 0;
+        for _ in 0..4 + next(16) {
+            match next(8) {
+                0 => {
+                    src.push('[');
+                    open += 1;
+                }
+                1 if open > 0 => {
+                    src.push(']');
+                    open -= 1;
+                }
+                _ => src.push_str(PIECES[next(PIECES.len())]),
+            }
+        }
+        src.push_str(&"]".repeat(open));
+        let input: Vec<u8> = (0..next(6)).map(|_| next(256) as u8).collect();
+
+        let ast = parse(lex(Cursor::new(src.as_str())));
+        if interpret(&ast, &input, FUEL).is_err() {
+            continue;
+        }
+        for level in [OptLevel::O1, OptLevel::O2] {
+            check(&src, &input, level);
+        }
+        checked += 1;
+    }
+}
+
+#[test]
+fn spreads_the_tape_when_temporaries_are_needed() {
+    use Instruction::*;
+    let check = BoundsCheck(BoundsRange {
+        start: 0,
+        length: 4,
+    });
+    // Nothing is known to be zero once the first loop has run.
+    let program = Program(vec![
+        check,
+        Input(0),
+        Loop(true, 0, vec![Modify(1, 3), Modify(0, -1)]),
+        Input(0),
+        Print(b"ok".to_vec()),
+        AddMultiple {
+            target: 2,
+            base: 0,
+            factor: 2,
+        },
+        Copy {
+            target: 3,
+            base: 0,
+            factor: -1,
+        },
+        If(true, 0, vec![Modify(1, 1)]),
+        Output(0),
+        Output(1),
+        Output(2),
+        Output(3),
+    ]);
+
+    let input = [2, 7];
+    let mut expected = Vec::new();
+    TreeExec::new(&mut expected, &input[..])
+        .exec_program(&program)
+        .unwrap();
+
+    let emitted = emit(&program).unwrap();
+    let actual = interpret(&parse(lex(Cursor::new(emitted.as_str()))), &input, FUEL).unwrap();
+    assert_eq!(actual.output, expected);
+    assert_eq!(expected, [b'o', b'k', 7, 7, 14, 249]);
+}
+
+#[test]
+fn unbalanced_ifs_must_clear_their_last_cell() {
+    use Instruction::*;
+    let moves = |body| Program(vec![Input(0), If(false, 0, body)]);
+    assert_eq!(
+        emit(&moves(vec![Move(1), Modify(0, 1)])),
+        Err(EmitError::UnbalancedIf)
+    );
+    assert_eq!(
+        emit(&moves(vec![Set(0, 0), Move(1), Set(0, 0)])).unwrap(),
+        ",[[-]>[-]]"
+    );
+}
@@ -0,0 +1,133 @@
+use rustfck::frontend::{
+    format::{format, minify, FormatOptions},
+    lexer::{lex, Dialect},
+    parser::parse,
+    reference::interpret,
+};
+use std::{fs, io::Cursor, path::Path};
+
+const NARROW: FormatOptions = FormatOptions {
+    width: 12,
+    indent: 2,
+    comments: true,
+};
+
+fn programs() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "b"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|p| {
+            let input = fs::read(p.with_extension("in")).unwrap_or_default();
+            (fs::read_to_string(p).unwrap(), input)
+        })
+        .collect()
+}
+
+fn run(src: &str, input: &[u8]) -> Vec<u8> {
+    interpret(&parse(lex(Cursor::new(src))), input, 1_000_000)
+        .unwrap()
+        .output
+}
+
+#[test]
+fn indents_loops_by_depth() {
+    let src = "set up ++[>+++[->++<]<-] then print >>.";
+    assert_eq!(
+        format(src, Dialect::default(), NARROW).unwrap(),
+        "set up\n\
+         ++\n\
+         [\n\
+         \x20 >+++\n\
+         \x20 [->++<]<-\n\
+         ]\n\
+         then print\n\
+         >>.\n"
+    );
+}
+
+#[test]
+fn wraps_runs_and_comments_at_the_width() {
+    let src = "a long comment that needs wrapping\n+++++++++++++++[-]++++";
+    assert_eq!(
+        format(src, Dialect::default(), NARROW).unwrap(),
+        "a long\ncomment that\nneeds\nwrapping\n++++++++++++\n+++[-]++++\n"
+    );
+}
+
+#[test]
+fn strips_comments_on_request() {
+    let options = FormatOptions {
+        comments: false,
+        ..FormatOptions::default()
+    };
+    let src = "[ clear ] + [ loop with comment . ]";
+    assert_eq!(
+        format(src, Dialect::default(), options).unwrap(),
+        "[]+[.]\n"
+    );
+}
+
+#[test]
+fn formatting_is_idempotent_and_preserves_behaviour() {
+    for (src, input) in programs() {
+        for options in [FormatOptions::default(), NARROW] {
+            let formatted = format(&src, Dialect::default(), options).unwrap();
+            assert_eq!(
+                format(&formatted, Dialect::default(), options).unwrap(),
+                formatted
+            );
+            assert!(formatted
+                .lines()
+                .all(|l| l.len() <= options.width || !l.contains(['+', '-', '<', '>'])));
+            assert_eq!(run(&formatted, &input), run(&src, &input));
+        }
+    }
+}
+
+#[test]
+fn reports_unmatched_brackets() {
+    let dialect = Dialect::default();
+    let options = FormatOptions::default();
+    assert_eq!(
+        format("+\n+]", dialect, options),
+        Err("unmatched ] at 2:2".to_owned())
+    );
+    assert_eq!(
+        format("[[]", dialect, options),
+        Err("unmatched [ at 1:1".to_owned())
+    );
+}
+
+#[test]
+fn keeps_embedded_input_verbatim() {
+    let dialect = Dialect {
+        debug_dump: true,
+        input_separator: true,
+    };
+    let src = "read ,[.,] # ! [raw input]\n";
+    assert_eq!(
+        format(src, dialect, FormatOptions::default()).unwrap(),
+        "read\n,[.,]#\n! [raw input]\n"
+    );
+    assert_eq!(minify(src, dialect), ",[.,]#! [raw input]\n");
+}
+
+#[test]
+fn minify_cancels_adjacent_pairs() {
+    let dialect = Dialect::default();
+    assert_eq!(minify("+ + - > < <\n> - [ ] +-", dialect), "[]");
+    assert_eq!(minify("+-[<>]-+", dialect), "[]");
+    assert_eq!(minify("+>-<+", dialect), "+>-<+");
+
+    for (src, input) in programs() {
+        let minified = minify(&src, dialect);
+        assert!(minified.len() <= src.len());
+        assert_eq!(run(&minified, &input), run(&src, &input));
+    }
+}
@@ -0,0 +1,228 @@
+//! Compares the optimizing pipeline against the AST reference interpreter on
+//! randomly generated programs, shrinking any mismatch to a minimal program.
+//! Set `FUZZ_SEED` and `FUZZ_CASES` to explore beyond the default run.
+
+use rustfck::{
+    frontend::{
+        code_gen::gen_program, lexer::lex, optimize::apply_optimizations, parser::parse,
+        reference::interpret,
+    },
+    ir::{exec::Exec, optimize::optimize_module},
+};
+use std::{
+    env,
+    io::Cursor,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+mod common;
+use common::trimmed;
+
+const FUEL: usize = 20_000;
+const TEMPLATES: &[&str] = &[
+    "[-]",
+    "[->+<]",
+    "[->>+<<]",
+    "[->++>+++<<]",
+    "[>+<--]",
+    "[-]>[-<+>]<",
+];
+
+struct Rng(u64);
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+fn gen_body(rng: &mut Rng, depth: usize, len: u64, pointer: &mut isize, src: &mut String) {
+    for _ in 0..len {
+        match rng.below(14) {
+            0..=2 => src.push_str(&"+".repeat(1 + rng.below(4) as usize)),
+            3..=4 => src.push_str(&"-".repeat(1 + rng.below(4) as usize)),
+            5 | 6 => {
+                src.push('>');
+                *pointer += 1;
+            }
+            7 if *pointer > 0 => {
+                src.push('<');
+                *pointer -= 1;
+            }
+            8 => src.push('.'),
+            9 => src.push(','),
+            10 => src.push_str(TEMPLATES[rng.below(TEMPLATES.len() as u64) as usize]),
+            11..=13 if depth < 3 => {
+                let start = *pointer;
+                src.push('[');
+                let len = 1 + rng.below(6);
+                gen_body(rng, depth + 1, len, pointer, src);
+                if rng.below(5) != 0 {
+                    let diff = *pointer - start;
+                    let fix = if diff > 0 { '<' } else { '>' };
+                    src.push_str(&fix.to_string().repeat(diff.unsigned_abs()));
+                    *pointer = start;
+                }
+                src.push(']');
+            }
+            _ => src.push('+'),
+        }
+    }
+}
+
+fn gen_case(rng: &mut Rng) -> (String, Vec<u8>) {
+    let mut src = String::new();
+    let len = 4 + rng.below(20);
+    gen_body(rng, 0, len, &mut 0, &mut src);
+    let input = (0..rng.below(8)).map(|_| rng.next() as u8).collect();
+    (src, input)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Verdict {
+    Discard,
+    Pass,
+    Fail(String),
+}
+
+fn check(src: &str, input: &[u8]) -> Verdict {
+    let ast = parse(lex(Cursor::new(src)));
+    let Ok(expected) = interpret(&ast, input, FUEL) else {
+        return Verdict::Discard;
+    };
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut program = ast.gen_expr_tree();
+        apply_optimizations(&mut program);
+        let mut module = gen_program(&program);
+        optimize_module(&mut module);
+
+        let mut output = Vec::new();
+        let mut exec = Exec::new(&mut output, input);
+        exec.exec_program(&module).unwrap();
+        let tape = exec.cells().to_vec();
+        (output, tape)
+    }));
+
+    let Ok((output, tape)) = result else {
+        return Verdict::Fail("the optimized program panicked".to_owned());
+    };
+    let tape = trimmed(&tape);
+
+    if output != expected.output {
+        Verdict::Fail(format!("output {output:?}, expected {:?}", expected.output))
+    } else if tape != expected.tape {
+        Verdict::Fail(format!("tape {tape:?}, expected {:?}", expected.tape))
+    } else {
+        Verdict::Pass
+    }
+}
+
+fn candidates(src: &str, input: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let bytes = src.as_bytes();
+    let mut out = Vec::new();
+
+    for (i, &c) in bytes.iter().enumerate() {
+        if c == b'[' {
+            let close = matching_close(bytes, i);
+            let whole = [&src[..i], &src[close + 1..]].concat();
+            let unwrapped = [&src[..i], &src[i + 1..close], &src[close + 1..]].concat();
+            out.push((whole, input.to_vec()));
+            out.push((unwrapped, input.to_vec()));
+        } else if c != b']' {
+            out.push(([&src[..i], &src[i + 1..]].concat(), input.to_vec()));
+        }
+    }
+    for i in 0..input.len() {
+        out.push((src.to_owned(), [&input[..i], &input[i + 1..]].concat()));
+    }
+
+    out
+}
+fn matching_close(bytes: &[u8], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, &c) in bytes.iter().enumerate().skip(open) {
+        match c {
+            b'[' => depth += 1,
+            b']' if depth == 1 => return i,
+            b']' => depth -= 1,
+            _ => (),
+        }
+    }
+    unreachable!("generated programs are balanced")
+}
+
+fn shrink(mut src: String, mut input: Vec<u8>) -> (String, Vec<u8>, String) {
+    let mut reason = match check(&src, &input) {
+        Verdict::Fail(reason) => reason,
+        verdict => panic!("cannot shrink a case that does not fail: {verdict:?}"),
+    };
+
+    'shrinking: loop {
+        for (smaller_src, smaller_input) in candidates(&src, &input) {
+            if let Verdict::Fail(smaller_reason) = check(&smaller_src, &smaller_input) {
+                src = smaller_src;
+                input = smaller_input;
+                reason = smaller_reason;
+                continue 'shrinking;
+            }
+        }
+        return (src, input, reason);
+    }
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[test]
+fn optimizer_matches_reference_interpreter() {
+    let seed = env_or("FUZZ_SEED", 0x5eed_cafe_f00d);
+    let cases = env_or("FUZZ_CASES", 400);
+    let mut rng = Rng(seed | 1);
+    let mut checked = 0;
+
+    for _ in 0..cases {
+        let (src, input) = gen_case(&mut rng);
+        match check(&src, &input) {
+            Verdict::Discard => (),
+            Verdict::Pass => checked += 1,
+            Verdict::Fail(_) => {
+                let (src, input, reason) = shrink(src, input);
+                panic!("miscompiled {src:?} with input {input:?}: {reason}");
+            }
+        }
+    }
+
+    assert!(
+        checked > cases / 4,
+        "only {checked} of {cases} cases terminated"
+    );
+}
+
+#[test]
+fn previously_shrunk_failures_pass() {
+    let cases: &[(&str, &[u8])] = &[(">+[---<[>>+<<],>]+[->+<]", b""), (",[-]><+[-]", &[154])];
+    for &(src, input) in cases {
+        assert_eq!(check(src, input), Verdict::Pass, "{src}");
+    }
+}
+
+#[test]
+fn shrinking_finds_minimal_program() {
+    let (src, input) = ("+>,[->+<]>.<<.", vec![7]);
+    assert_eq!(check(src, &input), Verdict::Pass);
+    let smaller = candidates(src, &input);
+    assert!(smaller.contains(&(">,[->+<]>.<<.".to_owned(), vec![7])));
+    assert!(smaller.contains(&("+>,->+<>.<<.".to_owned(), vec![7])));
+    assert!(smaller.contains(&("+>,>.<<.".to_owned(), vec![7])));
+    assert!(smaller.contains(&(src.to_owned(), vec![])));
+}
@@ -0,0 +1,107 @@
+use rustfck::ir::{
+    block::BlockID,
+    builder::Builder,
+    instruction::{Expr, Instruction, TestOp},
+    optimize::optimize_module,
+    printing::Printer,
+    types::Type,
+    Module,
+};
+
+mod common;
+use common::run_both;
+
+/// A module that outputs the results of the new operations on constants.
+fn module() -> (Module, BlockID) {
+    let mut module = Module::new();
+    let entry = module.add_block();
+    module.set_entry_block(entry);
+
+    let mut b = Builder::new(&mut module, entry);
+    let a = b.set(0xf0u8);
+    let mut values = vec![
+        b.shl(a, 2u8),
+        b.lshr(a, 4u8),
+        b.ashr(a, 4u8),
+        b.shl(a, 8u8),
+        b.lshr(a, 9u8),
+        b.ashr(a, 200u8),
+    ];
+
+    let tests = [
+        b.test(TestOp::ULessThan, a, 0x10u8),
+        b.test(TestOp::ULessEqual, a, a),
+        b.test(TestOp::ILessThan, a, 0x10u8),
+        b.test(TestOp::ILessEqual, 0x10u8, a),
+        b.test(TestOp::ILessThan, true, false),
+        b.test(TestOp::ULessThan, true, false),
+    ];
+    for t in tests {
+        values.push(b.zext(t, Type::I8));
+    }
+
+    let signed = b.sext(a, Type::I64);
+    let high = b.lshr(signed, 56u64);
+    values.push(b.trunc(high, Type::I8));
+    let unsigned = b.zext(a, Type::I64);
+    let high = b.lshr(unsigned, 4u64);
+    values.push(b.trunc(high, Type::I8));
+    let one = b.set(1u64);
+    let gone = b.shl(one, 64u64);
+    values.push(b.trunc(gone, Type::I8));
+    values.push(b.sext(true, Type::I8));
+    let odd = b.trunc(3u8, Type::I1);
+    values.push(b.zext(odd, Type::I8));
+
+    for v in values {
+        b.output(v);
+    }
+    (module, entry)
+}
+
+const EXPECTED: &[u8] = &[
+    0xc0, 0x0f, 0xff, 0, 0, 0xff, // shifts
+    0, 1, 1, 0, 1, 0, // comparisons
+    0xff, 0x0f, 0, 0xff, 1, // casts
+];
+
+#[test]
+fn exec_and_vm_agree() {
+    assert_eq!(run_both(&module().0), EXPECTED);
+}
+
+#[test]
+fn constants_are_folded() {
+    let (mut module, entry) = module();
+    optimize_module(&mut module);
+    let computed = module.block(entry).unwrap().body().iter().any(|i| {
+        matches!(
+            i,
+            Instruction::Assign(_, Expr::Binary(..) | Expr::Test(..) | Expr::Cast(..))
+        )
+    });
+    assert!(!computed);
+    assert_eq!(run_both(&module), EXPECTED);
+}
+
+#[test]
+fn operations_are_printed() {
+    let mut out = Vec::new();
+    Printer::new(&mut out).print_module(&module().0).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    for op in [
+        "shl i8",
+        "lshr i8",
+        "ashr i8",
+        "tult i8",
+        "tule i8",
+        "tslt i8",
+        "tsle i8",
+        "sext i8",
+        "zext i1",
+        "trunc i64",
+        "to i64",
+    ] {
+        assert!(text.contains(op), "{op} missing from\n{text}");
+    }
+}
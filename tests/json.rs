@@ -0,0 +1,60 @@
+#![cfg(feature = "serde")]
+
+use rustfck::{
+    frontend::{
+        ast::Ast, code_gen::gen_program, expr_tree::Program, lexer::lex, optimize, parser::parse,
+    },
+    ir::{self, Module},
+    pass::OptLevel,
+};
+use serde_json::{json, Value};
+use std::io::Cursor;
+
+mod common;
+use common::compile;
+
+const SRC: &str = "+[>+[>+<-]<-]>[>]<.";
+
+#[test]
+fn ast_round_trips() {
+    let ast = parse(lex(Cursor::new(SRC)));
+    let json = serde_json::to_string(&ast).unwrap();
+    assert_eq!(serde_json::from_str::<Ast>(&json).unwrap(), ast);
+
+    let value = serde_json::to_value(parse(lex(Cursor::new("+[-]>."))).0).unwrap();
+    assert_eq!(
+        value,
+        json!([{"Modify": 1}, {"Set": 0}, {"Move": 1}, "Output"])
+    );
+}
+
+#[test]
+fn expr_tree_round_trips() {
+    let mut program = compile(SRC);
+    optimize::pass_manager(OptLevel::O2).run(&mut program);
+    let json = serde_json::to_string(&program).unwrap();
+    assert_eq!(serde_json::from_str::<Program>(&json).unwrap(), program);
+}
+
+#[test]
+fn module_round_trips_with_types_and_parameters() {
+    let program = compile(SRC);
+    let mut module = gen_program(&program);
+    ir::optimize::pass_manager(OptLevel::O2).run(&mut module);
+
+    let value = serde_json::to_value(&module).unwrap();
+    assert_eq!(value["entry"], json!(0));
+    let blocks = value["blocks"].as_array().unwrap();
+    let parameter = blocks
+        .iter()
+        .find_map(|b| b["parameters"].as_array().unwrap().first().cloned())
+        .unwrap();
+    let register = &value["registers"][parameter.as_u64().unwrap() as usize];
+    assert_eq!(register["register_type"], json!("I64"));
+    assert!(blocks
+        .iter()
+        .flat_map(|b| b["body"].as_array().unwrap())
+        .any(|i| matches!(i, Value::Object(o) if o.contains_key("Branch"))));
+
+    assert_eq!(serde_json::from_value::<Module>(value).unwrap(), module);
+}
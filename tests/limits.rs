@@ -0,0 +1,219 @@
+use rustfck::{
+    frontend::{
+        code_gen::gen_program, exec::TreeExec, expr_tree::Program, optimize::limited_pass_manager,
+    },
+    ir::{
+        self,
+        bytecode::lower,
+        exec::Exec,
+        limits::{ExecError, Limits},
+        vm::Vm,
+        Module,
+    },
+    pass::OptLevel,
+};
+use std::{
+    io::{empty, sink},
+    mem,
+    time::{Duration, Instant},
+};
+
+mod common;
+use common::{compile, compile_module};
+
+fn compile_limited(src: &str, level: OptLevel, limits: Limits) -> (Program, Module) {
+    let mut program = compile(src);
+    limited_pass_manager(level, limits).run(&mut program);
+    let mut module = gen_program(&program);
+    ir::optimize::pass_manager(level).run(&mut module);
+    (program, module)
+}
+
+/// Runs `src` on Exec, the VM and TreeExec, returning each one's result and
+/// output.
+fn run_all(src: &str, level: OptLevel, limits: Limits) -> [(Result<(), ExecError>, Vec<u8>); 3] {
+    let (program, module) = compile_limited(src, level, limits);
+
+    let mut exec_out = Vec::new();
+    let mut exec = Exec::new(&mut exec_out, empty());
+    exec.set_limits(limits);
+    let exec_result = exec.exec_program(&module);
+
+    let mut vm_out = Vec::new();
+    let mut vm = Vm::new(&mut vm_out, empty());
+    vm.set_limits(limits);
+    let vm_result = vm.exec(&lower(&module).unwrap());
+
+    let mut tree = TreeExec::new(Vec::new(), empty());
+    tree.set_limits(limits);
+    let tree_result = tree.exec_program(&program);
+
+    [
+        (exec_result, exec_out),
+        (vm_result, vm_out),
+        (tree_result, tree.stdout().clone()),
+    ]
+}
+
+const LEVELS: [OptLevel; 2] = [OptLevel::O0, OptLevel::O2];
+
+#[test]
+fn infinite_loops_run_out_of_fuel() {
+    let limits = Limits {
+        fuel: Some(1000),
+        ..Limits::default()
+    };
+    for level in LEVELS {
+        for (result, _) in run_all("+[]", level, limits) {
+            let Err(ExecError::OutOfFuel(progress)) = result else {
+                panic!("{level}: expected to run out of fuel, got {result:?}")
+            };
+            assert_eq!(progress.blocks, 1001, "{level}");
+        }
+    }
+}
+
+#[test]
+fn exec_and_vm_charge_the_same_fuel() {
+    let src = "++++[>++++++++<-]>[.-]";
+    for level in LEVELS {
+        let module = compile_module(src, level);
+        let mut exec = Exec::new(sink(), empty());
+        exec.enable_profiling();
+        exec.exec_program(&module).unwrap();
+        let cost = exec.profile().unwrap().blocks.iter().sum::<u64>();
+
+        for fuel in [cost, cost - 1] {
+            let limits = Limits {
+                fuel: Some(fuel),
+                ..Limits::default()
+            };
+            let [exec, vm, _] = run_all(src, level, limits);
+            assert_eq!(exec.1, vm.1, "{level} with {fuel} fuel");
+            match (exec.0, vm.0) {
+                (Ok(()), Ok(())) => assert_eq!(fuel, cost, "{level}"),
+                (Err(ExecError::OutOfFuel(exec)), Err(ExecError::OutOfFuel(vm))) => {
+                    assert_eq!(fuel, cost - 1, "{level}");
+                    assert_eq!(exec.blocks, vm.blocks, "{level}");
+                    assert_eq!(exec.instructions, vm.instructions, "{level}");
+                }
+                results => panic!("{level} with {fuel} fuel: {results:?}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn infinite_loops_time_out() {
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    };
+    let start = Instant::now();
+    for level in LEVELS {
+        for (result, _) in run_all("+[]", level, limits) {
+            assert!(
+                matches!(result, Err(ExecError::Timeout(_))),
+                "{level}: {result:?}"
+            );
+        }
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn tape_growth_is_capped() {
+    let limits = Limits {
+        max_tape: Some(64),
+        ..Limits::default()
+    };
+    let [o0, o2] = LEVELS.map(|level| run_all("+[.>+]", level, limits));
+    for ((o0, o0_out), (o2, o2_out)) in o0.into_iter().zip(o2) {
+        let (Err(ExecError::TapeLimit(o0)), Err(ExecError::TapeLimit(o2))) = (&o0, &o2) else {
+            panic!("expected to hit the tape limit, got {o0:?} and {o2:?}")
+        };
+        assert_eq!(o0_out, [1; 64]);
+        assert_eq!(o2_out, [1; 64]);
+        assert_eq!(o0.tape, 64);
+        assert_eq!(o2.tape, 64);
+        assert_eq!(o0.blocks, o2.blocks);
+    }
+}
+
+#[test]
+fn terminating_programs_fit_in_their_limits() {
+    let limits = Limits {
+        fuel: Some(10_000),
+        timeout: Some(Duration::from_secs(10)),
+        max_tape: Some(16),
+    };
+    for level in LEVELS {
+        for (result, out) in run_all(",+++++[>++<-]>[.-]", level, limits) {
+            assert!(result.is_ok(), "{level}: {result:?}");
+            assert_eq!(out, (1..=10).rev().collect::<Vec<u8>>(), "{level}");
+        }
+    }
+}
+
+#[test]
+fn moving_left_of_the_tape_is_an_error() {
+    for src in ["<+.", "+>+[<+]", ">+[<<]"] {
+        for level in LEVELS {
+            for (result, _) in run_all(src, level, Limits::default()) {
+                assert!(
+                    matches!(result, Err(ExecError::LeftOfTape(_))),
+                    "{src} {level}: {result:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn opt_levels_agree_on_tape_limits() {
+    let src = format!(
+        ",[{}.{}-]{}.",
+        ">".repeat(20),
+        "<".repeat(20),
+        "+".repeat(49)
+    );
+    let limits = Limits {
+        max_tape: Some(10),
+        ..Limits::default()
+    };
+    let run = |level, input: &[u8]| {
+        let mut program = compile(&src);
+        limited_pass_manager(level, limits).run(&mut program);
+        let mut out = Vec::new();
+        let mut exec = Exec::new(&mut out, input);
+        exec.set_limits(limits);
+        let result = exec.exec_program(&gen_program(&program));
+        (result.map_err(|err| mem::discriminant(&err)), out)
+    };
+    for input in [&b"\0"[..], b"\x01"] {
+        assert_eq!(
+            run(OptLevel::O2, input),
+            run(OptLevel::O0, input),
+            "{input:?}"
+        );
+    }
+    assert_eq!(run(OptLevel::O2, b"\0"), (Ok(()), b"1".to_vec()));
+}
+
+#[test]
+fn output_before_the_tape_limit_is_kept_at_every_level() {
+    let src = format!("{}.{}+.", "+".repeat(65), ">".repeat(100));
+    let limits = Limits {
+        max_tape: Some(10),
+        ..Limits::default()
+    };
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        for (result, out) in run_all(&src, level, limits) {
+            assert!(
+                matches!(result, Err(ExecError::TapeLimit(_))),
+                "{level}: {result:?}"
+            );
+            assert_eq!(out, b"A", "{level}");
+        }
+    }
+}
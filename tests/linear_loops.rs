@@ -0,0 +1,120 @@
+use rustfck::frontend::{
+    expr_tree::{BoundsRange, Instruction, Program},
+    optimize::{apply_optimizations, recog_additions},
+};
+
+mod common;
+use common::{compile, contains_loop, run};
+
+fn linear_program(initial: &[u8], body: Vec<Instruction>) -> Program {
+    let mut i = vec![Instruction::BoundsCheck(BoundsRange {
+        start: 0,
+        length: initial.len(),
+    })];
+    for (cell, &val) in initial.iter().enumerate() {
+        i.push(Instruction::Set(cell as isize, val));
+    }
+    i.push(Instruction::Loop(true, 0, body));
+    for cell in 0..initial.len() {
+        i.push(Instruction::Output(cell as isize));
+    }
+    Program(i)
+}
+
+fn assert_recognized(program: Program) {
+    let expected = run(&program, &[]);
+    let mut optimized = program.clone();
+    recog_additions(&mut optimized);
+    assert!(
+        !contains_loop(&optimized.0),
+        "{program:?} was not recognized"
+    );
+    assert_eq!(run(&optimized, &[]), expected, "{program:?}");
+}
+
+#[test]
+fn every_odd_step_and_start_value() {
+    for step in (-127..=127i8).step_by(2) {
+        for start in 0..=255 {
+            use Instruction::*;
+            let body = vec![Modify(1, 3), Modify(0, step), Modify(2, -5)];
+            assert_recognized(linear_program(&[start, 7, 100], body));
+        }
+    }
+}
+
+#[test]
+fn split_base_modifications() {
+    for start in 0..=255 {
+        use Instruction::*;
+        let body = vec![Modify(0, 2), Modify(1, 1), Modify(0, 1), Modify(2, 9)];
+        assert_recognized(linear_program(&[start, 0, 0], body));
+    }
+}
+
+#[test]
+fn sets_of_other_cells() {
+    for start in 0..=255 {
+        use Instruction::*;
+        let body = vec![
+            Modify(1, 4),
+            Set(1, 5),
+            Modify(0, -3),
+            Modify(1, 2),
+            Modify(2, 1),
+        ];
+        assert_recognized(linear_program(&[start, 11, 13], body));
+    }
+}
+
+#[test]
+fn nested_copy_loops() {
+    for outer in 0..32 {
+        for inner in [0, 1, 2, 17, 255] {
+            let src = format!(
+                ",{}>{}<[>[->+>+<<]<---]>>>.<.<.",
+                "+".repeat(outer),
+                "+".repeat(inner)
+            );
+            let reference = compile(&src);
+            let mut optimized = reference.clone();
+            apply_optimizations(&mut optimized);
+
+            assert!(!contains_loop(&optimized.0), "{src} was not recognized");
+            assert_eq!(run(&optimized, &[]), run(&reference, &[]), "{src}");
+        }
+    }
+}
+
+#[test]
+fn non_linear_loops_are_kept() {
+    use Instruction::*;
+    let bodies = [
+        vec![Modify(0, -2), Modify(1, 1)],
+        vec![Modify(0, -1), Set(0, 3)],
+        vec![Modify(0, -1), Output(1)],
+        vec![
+            Modify(0, -1),
+            AddMultiple {
+                target: 1,
+                base: 2,
+                factor: 1,
+            },
+        ],
+        vec![
+            Modify(0, -1),
+            Set(1, 0),
+            AddMultiple {
+                target: 2,
+                base: 1,
+                factor: 1,
+            },
+        ],
+    ];
+
+    for body in bodies {
+        let mut program = linear_program(&[3, 1, 2], body);
+        recog_additions(&mut program);
+        assert!(contains_loop(&program.0), "{program:?} was recognized");
+    }
+}
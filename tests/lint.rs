@@ -0,0 +1,99 @@
+use rustfck::frontend::{
+    lexer::Dialect,
+    lint::{lint, LintKind},
+};
+use std::{fs, path::Path};
+
+fn kinds(src: &str) -> Vec<(LintKind, &str)> {
+    lint(src, Dialect::default())
+        .unwrap()
+        .into_iter()
+        .map(|l| (l.kind, &src[l.span.start..l.span.end]))
+        .collect()
+}
+
+#[test]
+fn sample_programs_are_clean() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|e| e == "b") {
+            let src = fs::read_to_string(&path).unwrap();
+            assert_eq!(kinds(&src), [], "{}", path.display());
+        }
+    }
+}
+
+#[test]
+fn dead_loops() {
+    use LintKind::*;
+    assert_eq!(
+        kinds("[comment, really.]+"),
+        [(DeadLoop, "[comment, really.]")]
+    );
+    assert_eq!(kinds(",[-][.]"), [(DeadLoop, "[.]")]);
+    assert_eq!(kinds(",[->+<]>[-]<[.]"), [(DeadLoop, "[.]")]);
+    assert_eq!(kinds("+>,[>]<[-]"), []);
+    assert_eq!(kinds("++[>+<-]>[<+>-]"), []);
+    assert_eq!(kinds("++[>[-]+<-]"), [], "later iterations see the +");
+    assert_eq!(kinds("++[>[.]+<-]"), [(InfiniteLoop, "[.]")]);
+}
+
+#[test]
+fn infinite_loops_and_unreachable_code() {
+    use LintKind::*;
+    assert_eq!(kinds(",[.]"), [(InfiniteLoop, "[.]")]);
+    assert_eq!(kinds(",[>]"), []);
+    assert_eq!(kinds(",[+]"), []);
+    assert_eq!(
+        kinds(",[><]"),
+        [(InfiniteLoop, "[><]"), (CancellingCommands, "><")]
+    );
+    assert_eq!(kinds(",[>+<.]"), [(InfiniteLoop, "[>+<.]")]);
+    assert_eq!(kinds(",[>[-]<]"), [(InfiniteLoop, "[>[-]<]")]);
+    assert_eq!(kinds(",[>+<<]"), []);
+    assert_eq!(kinds(",[>[>]<]"), []);
+    assert_eq!(kinds(",[>[<-]<]"), []);
+    assert_eq!(
+        kinds("+[]>.<"),
+        [(InfiniteLoop, "[]"), (UnreachableCode, ">.<")]
+    );
+    assert_eq!(
+        kinds("+[>+[]<.]."),
+        [
+            (InfiniteLoop, "[]"),
+            (UnreachableCode, "<."),
+            (UnreachableCode, "."),
+        ]
+    );
+}
+
+#[test]
+fn negative_pointer() {
+    use LintKind::*;
+    assert_eq!(kinds("+<"), [(NegativePointer, "<")]);
+    assert_eq!(kinds(">>.<<<.>"), [(NegativePointer, "<<<")]);
+    assert_eq!(kinds(">+[<]"), []);
+    assert_eq!(kinds(",[>]<<"), []);
+}
+
+#[test]
+fn cancelling_commands() {
+    use LintKind::*;
+    let src = "+ + - comment >< ++";
+    let lints = lint(src, Dialect::default()).unwrap();
+    assert_eq!(lints.len(), 2);
+    assert_eq!(lints[0].kind, CancellingCommands);
+    assert_eq!(
+        lints[0].to_string(),
+        "these +- commands amount to + [cancelling-commands]"
+    );
+    assert_eq!(lints[1].span.line_col(src), (1, 15));
+    assert_eq!(
+        lints[1].to_string(),
+        "these >< commands cancel out [cancelling-commands]"
+    );
+    assert_eq!(kinds(",[-]+-+"), [(CancellingCommands, "+-+")]);
+    assert!(lint("+]", Dialect::default()).is_err());
+    assert!(lint("[+", Dialect::default()).is_err());
+}
@@ -0,0 +1,97 @@
+use rustfck::frontend::{
+    expr_tree::{BoundsRange, Instruction},
+    optimize::apply_optimizations,
+    partial_eval::eval_constant_prefix,
+};
+
+mod common;
+use common::{compile, run};
+
+#[test]
+fn prefix_is_evaluated_up_to_input() {
+    let src = "++++++++[>++++++++<-]>+.+.>+++[<+>-],.<.";
+    let reference = compile(src);
+    let mut optimized = reference.clone();
+    eval_constant_prefix(&mut optimized, 10_000);
+
+    assert!(matches!(optimized.0[0], Instruction::BoundsCheck(_)));
+    assert!(optimized.0.contains(&Instruction::Print(b"AB".to_vec())));
+    assert!(optimized.0.contains(&Instruction::Set(1, b'E')));
+    assert!(!optimized
+        .0
+        .iter()
+        .any(|i| matches!(i, Instruction::Loop(..))));
+    assert_eq!(run(&optimized, b"x"), run(&reference, b"x"));
+}
+
+#[test]
+fn programs_without_input_become_constant() {
+    let src = ">++++++++[<+++++++++>-]<.>++++[<+++++++>-]<+.+++++++..+++.";
+    let mut optimized = compile(src);
+    apply_optimizations(&mut optimized);
+
+    assert!(optimized.0.contains(&Instruction::Print(b"Hello".to_vec())));
+    assert_eq!(run(&optimized, b""), b"Hello");
+}
+
+#[test]
+fn budget_leaves_long_prefixes_alone() {
+    let src = "++[>+++++[>++++++++++[>+<-]<-]<-]>>>.";
+    let reference = compile(src);
+    let mut optimized = reference.clone();
+    eval_constant_prefix(&mut optimized, 100);
+
+    assert!(optimized
+        .0
+        .iter()
+        .any(|i| matches!(i, Instruction::Loop(..))));
+    assert_eq!(run(&optimized, b""), run(&reference, b""));
+}
+
+#[test]
+fn left_of_origin_stops_evaluation() {
+    let mut program = compile("+++.<+>.");
+    eval_constant_prefix(&mut program, 1000);
+    assert!(program.0.contains(&Instruction::Print(vec![3])));
+    assert!(program.0.contains(&Instruction::Move(-1)));
+}
+
+#[test]
+fn empty_infinite_loops_spend_the_budget() {
+    let reference = compile("+.[]");
+    let mut optimized = reference.clone();
+    eval_constant_prefix(&mut optimized, 1000);
+    assert!(optimized.0.contains(&Instruction::Print(vec![1])));
+    assert!(optimized
+        .0
+        .iter()
+        .any(|i| matches!(i, Instruction::Loop(..))));
+
+    let mut program = compile("+[]");
+    apply_optimizations(&mut program);
+}
+
+#[test]
+fn loops_that_run_out_of_budget_are_undone() {
+    let reference = compile("+++>++<[->>>>+<<<<]>>>>.");
+    let mut optimized = reference.clone();
+    eval_constant_prefix(&mut optimized, 20);
+
+    let loop_at = optimized
+        .0
+        .iter()
+        .position(|i| matches!(i, Instruction::Loop(..)))
+        .unwrap();
+    assert_eq!(
+        optimized.0[..loop_at],
+        [
+            Instruction::BoundsCheck(BoundsRange {
+                start: 0,
+                length: 2
+            }),
+            Instruction::Set(0, 3),
+            Instruction::Set(1, 2),
+        ]
+    );
+    assert_eq!(run(&optimized, b""), run(&reference, b""));
+}
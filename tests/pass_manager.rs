@@ -0,0 +1,77 @@
+use rustfck::{
+    frontend::optimize,
+    pass::{OptLevel, PassManager, PassTarget},
+};
+use std::io::{self, Write};
+
+mod common;
+use common::compile;
+
+const SRC: &str = ",>++++++++[<+++++++++>-]<.>[-]<[->+<]>.";
+
+#[test]
+fn o0_leaves_program_alone() {
+    let mut program = compile(SRC);
+    let reference = program.clone();
+    let mut manager = optimize::pass_manager(OptLevel::O0);
+    assert!(!manager.run(&mut program));
+    assert_eq!(program, reference);
+    assert!(manager.stats().is_empty());
+}
+
+#[test]
+fn stats_count_runs_and_instructions() {
+    let mut program = compile(SRC);
+    let before = program.instruction_count();
+    let mut manager = optimize::pass_manager(OptLevel::O2);
+    manager.run(&mut program);
+    let after = program.instruction_count();
+
+    let removed: usize = manager.stats().iter().map(|s| s.removed).sum();
+    let added: usize = manager.stats().iter().map(|s| s.added).sum();
+    assert_eq!(before + added - removed, after);
+
+    let normalize = manager
+        .stats()
+        .iter()
+        .find(|s| s.name == "normalize-pointer-movement")
+        .unwrap();
+    assert_eq!(normalize.runs, 2);
+}
+
+#[test]
+fn disabled_passes_do_not_run() {
+    let mut program = compile(SRC);
+    let mut manager = optimize::pass_manager(OptLevel::O2);
+    manager.disable("recog-additions");
+    manager.run(&mut program);
+    assert!(manager.stats().iter().all(|s| s.name != "recog-additions"));
+}
+
+#[derive(Clone, PartialEq)]
+struct Counter(usize);
+impl PassTarget for Counter {
+    fn instruction_count(&self) -> usize {
+        self.0
+    }
+    fn dump(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", self.0)
+    }
+}
+
+#[test]
+fn iteration_cap_stops_runaway_passes() {
+    let mut manager = PassManager::new();
+    manager.add_pass("grow", |c: &mut Counter| {
+        c.0 += 1;
+        true
+    });
+    manager.set_max_iterations(5);
+
+    let mut counter = Counter(0);
+    assert!(manager.run(&mut counter));
+    assert_eq!(counter.0, 5);
+    assert_eq!(manager.iterations(), 5);
+    assert_eq!(manager.stats()[0].added, 5);
+    assert!(!manager.converged());
+}
@@ -0,0 +1,124 @@
+use rustfck::{
+    frontend::{
+        code_gen::{gen_program, gen_program_with_loops},
+        expr_tree::{BoundsRange, Instruction},
+        profile::{write_folded, write_report, LoopMap},
+    },
+    ir::{
+        exec::{Exec, Status},
+        profile::Profile,
+        Module,
+    },
+};
+use std::io::{empty, sink};
+
+mod common;
+use common::compile;
+
+fn compile_with_loops(src: &str) -> (Module, LoopMap) {
+    gen_program_with_loops(&compile(src))
+}
+
+fn profile(module: &Module) -> Profile {
+    let mut exec = Exec::new(sink(), empty());
+    exec.enable_profiling();
+    exec.exec_program(module).unwrap();
+    exec.profile().unwrap().clone()
+}
+
+#[test]
+fn counts_loop_iterations_and_instructions() {
+    let (module, loops) = compile_with_loops("+++[>++[>+<-]<-]>>.");
+    let profile = profile(&module);
+    let stats = loops.stats(&profile);
+
+    assert_eq!(loops.loops.len(), 2);
+    let (outer, inner) = (stats[0], stats[1]);
+    assert_eq!(loops.loops[1].parent, Some(0));
+    assert_eq!(outer.iterations, 3);
+    assert_eq!(inner.iterations, 6);
+    assert_eq!(
+        outer.total_instructions,
+        outer.self_instructions + inner.total_instructions
+    );
+    assert!(outer.total_instructions < profile.total_instructions());
+
+    assert_eq!(profile.output_bytes, 1);
+    assert_eq!(profile.tape_high_water, 3);
+    assert_eq!(
+        profile.instructions_per_output_byte(),
+        Some(profile.total_instructions() as f64)
+    );
+}
+
+#[test]
+fn stepping_counts_the_same_as_running() {
+    let (module, _) = compile_with_loops("++[>+++[>+<-]<-]");
+    let expected = profile(&module);
+
+    let mut exec = Exec::new(sink(), empty());
+    exec.enable_profiling();
+    exec.start(&module);
+    while exec.run(&module, Some(3)).unwrap() != Status::Halted {}
+    assert_eq!(exec.profile(), Some(&expected));
+}
+
+#[test]
+fn profiling_is_off_by_default() {
+    let (module, _) = compile_with_loops("+.");
+    let mut exec = Exec::new(sink(), empty());
+    exec.exec_program(&module).unwrap();
+    assert_eq!(exec.profile(), None);
+}
+
+#[test]
+fn folded_stacks_cover_every_instruction() {
+    let (module, loops) = compile_with_loops("++[>+++[>+<-]<-]>[-]");
+    let profile = profile(&module);
+
+    let mut folded = Vec::new();
+    write_folded(&mut folded, &loops, &profile).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+
+    let mut total = 0;
+    for line in folded.lines() {
+        let (stack, count) = line.rsplit_once(' ').unwrap();
+        assert!(stack.starts_with("main"), "{line}");
+        total += count.parse::<u64>().unwrap();
+    }
+    assert_eq!(total, profile.total_instructions());
+    assert!(folded.contains(&format!(
+        "main;{};{} ",
+        loops.loops[0].name(),
+        loops.loops[1].name()
+    )));
+}
+
+#[test]
+fn report_lists_the_hottest_loop_first() {
+    let (module, loops) = compile_with_loops("+[>++++[>+<-]<-]>>++++++++[>+<-]");
+    let profile = profile(&module);
+
+    let mut report = Vec::new();
+    write_report(&mut report, &loops, &profile).unwrap();
+    let report = String::from_utf8(report).unwrap();
+
+    let hottest = report.lines().skip_while(|l| !l.contains("iterations"));
+    let first = hottest.clone().nth(1).unwrap();
+    assert!(first.contains(&loops.loops[2].name()), "{report}");
+    assert!(report.contains("tape high-water mark: 4 cells"), "{report}");
+}
+
+#[test]
+fn high_water_mark_counts_cells_in_use() {
+    let mut program = compile("+>+>+[-]<<.");
+    program.0.insert(
+        0,
+        Instruction::BoundsCheck(BoundsRange {
+            start: 0,
+            length: 100,
+        }),
+    );
+    let profile = profile(&gen_program(&program));
+    assert_eq!(profile.tape_high_water, 3);
+}
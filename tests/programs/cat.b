@@ -0,0 +1,2 @@
+Copies stdin to stdout until a NUL byte
+,[.,]
@@ -0,0 +1,2 @@
+Reverses a NUL terminated line
+>,[>,]<[.<]
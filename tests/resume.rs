@@ -0,0 +1,193 @@
+use rustfck::{
+    ir::{
+        builder::Builder,
+        exec::{Exec, Position, Status, Value},
+        instruction::TargetBlock,
+        limits::{ExecError, Limits},
+        snapshot::Snapshot,
+        types::Type,
+        Module,
+    },
+    pass::OptLevel,
+};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, empty, Read},
+    rc::Rc,
+};
+
+mod common;
+use common::compile_module;
+
+/// Input that arrives over time; reads block until it is closed.
+#[derive(Clone, Default)]
+struct Pipe(Rc<RefCell<(VecDeque<u8>, bool)>>);
+impl Pipe {
+    fn push(&self, bytes: &[u8]) {
+        self.0.borrow_mut().0.extend(bytes);
+    }
+    fn close(&self) {
+        self.0.borrow_mut().1 = true;
+    }
+}
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (queue, closed) = &mut *self.0.borrow_mut();
+        match queue.pop_front() {
+            Some(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            None if *closed => Ok(0),
+            None => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+#[test]
+fn stepping_matches_a_full_run() {
+    let module = compile_module(">,[>,]<[.<]", OptLevel::O0);
+
+    let mut expected = Vec::new();
+    let mut exec = Exec::new(&mut expected, &b"hello"[..]);
+    exec.exec_program(&module).unwrap();
+    let expected_cells = exec.cells().to_vec();
+
+    let mut output = Vec::new();
+    let mut exec = Exec::new(&mut output, &b"hello"[..]);
+    exec.start(&module);
+    let mut pauses = 0;
+    while exec.run(&module, Some(1)).unwrap() == Status::OutOfSteps {
+        pauses += 1;
+    }
+    let cells = exec.cells().to_vec();
+
+    assert!(pauses > 10);
+    assert_eq!(cells, expected_cells);
+    assert_eq!(output, b"olleh");
+}
+
+#[test]
+fn input_that_is_not_ready_pauses() {
+    let module = compile_module(",.,.", OptLevel::O0);
+    let pipe = Pipe::default();
+    let mut output = Vec::new();
+    let mut exec = Exec::new(&mut output, pipe.clone());
+
+    exec.start(&module);
+    assert_eq!(exec.run(&module, None).unwrap(), Status::NeedsInput);
+    pipe.push(b"a");
+    assert_eq!(exec.run(&module, None).unwrap(), Status::NeedsInput);
+    pipe.push(b"b");
+    pipe.close();
+    assert_eq!(exec.run(&module, None).unwrap(), Status::Halted);
+    drop(exec);
+
+    assert_eq!(output, b"ab");
+}
+
+#[test]
+fn snapshots_resume_in_a_fresh_exec() {
+    let module = compile_module("++++++++[>++++++++<-]>+.+.+.", OptLevel::O0);
+
+    let mut first = Vec::new();
+    let mut exec = Exec::new(&mut first, empty());
+    exec.start(&module);
+    assert_eq!(exec.run(&module, Some(40)).unwrap(), Status::OutOfSteps);
+
+    let mut bytes = Vec::new();
+    let snapshot = exec.snapshot();
+    snapshot.write_to(&mut bytes).unwrap();
+    let restored = Snapshot::read_from(&bytes[..]).unwrap();
+    assert_eq!(restored, snapshot);
+
+    let mut rest = Vec::new();
+    let mut exec = Exec::new(&mut rest, empty());
+    exec.restore(&module, restored).unwrap();
+    assert_eq!(exec.run(&module, None).unwrap(), Status::Halted);
+
+    first.extend(rest);
+    assert_eq!(first, b"ABC");
+}
+
+#[test]
+fn corrupt_snapshots_are_rejected() {
+    assert!(Snapshot::read_from(&b"nope"[..]).is_err());
+    assert!(Snapshot::read_from(&b"BFSN\x01\x07"[..]).is_err());
+    assert!(Snapshot::read_from(&b"BFSN\x02\x07"[..]).is_err());
+}
+
+#[test]
+fn snapshots_of_other_programs_are_rejected() {
+    let small = compile_module("+.", OptLevel::O0);
+    let module = compile_module("++++++++[>++++++++<-]>+.+.+.", OptLevel::O0);
+    let mut exec = Exec::new(io::sink(), empty());
+    exec.start(&module);
+    exec.run(&module, Some(40)).unwrap();
+    let snapshot = exec.snapshot();
+
+    let mut fresh = Exec::new(io::sink(), empty());
+    assert!(fresh.restore(&small, snapshot.clone()).is_err());
+
+    let mut past_the_end = snapshot.clone();
+    let (Position::At(block, _) | Position::Jump(block, _)) = snapshot.position else {
+        panic!("{:?}", snapshot.position)
+    };
+    past_the_end.position = Position::At(block, usize::MAX);
+    assert!(fresh.restore(&module, past_the_end).is_err());
+}
+
+#[test]
+fn snapshots_with_mistyped_values_are_rejected() {
+    let mut module = Module::new();
+    let entry = module.add_block();
+    let exit = module.add_block();
+    module.set_entry_block(entry);
+    let mut b = Builder::new(&mut module, entry);
+    b.jump(TargetBlock::new(exit, vec![1u8.into()]));
+    b.select_block(exit);
+    let x = b.add_parameter(Type::I8);
+    b.output(x);
+
+    let mut exec = Exec::new(io::sink(), empty());
+    exec.start(&module);
+    let snapshot = exec.snapshot();
+    let restore = |snapshot| Exec::new(io::sink(), empty()).restore(&module, snapshot);
+
+    let mut jump = snapshot.clone();
+    jump.position = Position::Jump(exit, vec![Value::I8(1)]);
+    assert!(restore(jump.clone()).is_ok());
+    jump.position = Position::Jump(exit, vec![Value::I1(true)]);
+    assert!(restore(jump).is_err());
+
+    let mut register = snapshot.clone();
+    register.registers[usize::from(x)] = Value::I8(1);
+    assert!(restore(register.clone()).is_ok());
+    register.registers[usize::from(x)] = Value::I64(1);
+    assert!(restore(register).is_err());
+}
+
+#[test]
+fn snapshots_keep_the_fuel_spent() {
+    let module = compile_module("+[>+<+]", OptLevel::O0);
+    let limits = Limits {
+        fuel: Some(100),
+        ..Limits::default()
+    };
+    let mut exec = Exec::new(io::sink(), empty());
+    exec.set_limits(limits);
+    exec.start(&module);
+    assert_eq!(exec.run(&module, Some(200)).unwrap(), Status::OutOfSteps);
+    let snapshot = exec.snapshot();
+    assert!(snapshot.progress.blocks > 0);
+
+    let mut resumed = Exec::new(io::sink(), empty());
+    resumed.set_limits(limits);
+    resumed.restore(&module, snapshot.clone()).unwrap();
+    assert_eq!(resumed.snapshot().progress.blocks, snapshot.progress.blocks);
+    match resumed.run(&module, None) {
+        Err(ExecError::OutOfFuel(p)) => assert_eq!(p.blocks, 101),
+        other => panic!("{other:?}"),
+    }
+}
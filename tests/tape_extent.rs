@@ -0,0 +1,61 @@
+use rustfck::frontend::{
+    expr_tree::{BoundsRange, Instruction},
+    optimize::{allocate_static_tape, apply_optimizations, tape_extent},
+};
+
+mod common;
+use common::{compile, run};
+
+fn checks(instructions: &[Instruction]) -> usize {
+    instructions
+        .iter()
+        .map(|i| match i {
+            Instruction::BoundsCheck(_) => 1,
+            Instruction::Loop(_, _, body) | Instruction::If(_, _, body) => checks(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[test]
+fn balanced_programs_have_known_extent() {
+    let extent = |src| tape_extent(&compile(src));
+    let range = |start, length| Some(BoundsRange { start, length });
+    assert_eq!(extent("+>>+<."), range(0, 3));
+    assert_eq!(extent(",[->>+<<]>>."), range(0, 3));
+    assert_eq!(extent(">>>,[<<+<+>>>-]"), range(0, 4));
+    assert_eq!(extent("<+"), range(-1, 1));
+    assert_eq!(extent(""), None);
+}
+
+#[test]
+fn unbounded_loops_keep_their_checks() {
+    for src in [",[>,]<[.<]", "+[>+]", ">,[[-]<]+>."] {
+        let mut program = compile(src);
+        assert_eq!(tape_extent(&program), None, "{src}");
+        let before = program.clone();
+        allocate_static_tape(&mut program);
+        assert_eq!(program, before, "{src}");
+    }
+}
+
+#[test]
+fn checks_collapse_into_one_allocation() {
+    let src = ",[->+>++<<]>[-<+>]>.<<.";
+    let mut program = compile(src);
+    allocate_static_tape(&mut program);
+    assert_eq!(checks(&program.0), 1);
+    assert_eq!(
+        program.0[0],
+        Instruction::BoundsCheck(BoundsRange {
+            start: 0,
+            length: 3
+        })
+    );
+    assert_eq!(run(&program, b"\x05"), run(&compile(src), b"\x05"));
+
+    let mut optimized = compile(src);
+    apply_optimizations(&mut optimized);
+    assert_eq!(checks(&optimized.0), 1);
+    assert_eq!(run(&optimized, b"\x05"), b"\x0a\x05");
+}
@@ -0,0 +1,199 @@
+use rustfck::{
+    ir::{
+        exec::Exec,
+        trace::{diff, diff_end_states, Divergence, Event, Replay, TraceReader},
+        Module,
+    },
+    pass::OptLevel,
+};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+mod common;
+use common::compile_module;
+
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn record(module: &Module, input: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let trace = Shared::default();
+    let mut output = Vec::new();
+    let mut exec = Exec::new(&mut output, input);
+    exec.record_trace(trace.clone()).unwrap();
+    exec.exec_program(module).unwrap();
+    let cells = exec.cells().to_vec();
+    drop(exec);
+    let trace = std::mem::take(&mut *trace.0.lock().unwrap());
+    (trace, output, cells)
+}
+
+fn replay(trace: &[u8]) -> Replay {
+    Replay::new(TraceReader::new(trace).unwrap()).unwrap()
+}
+
+fn diff_traces(left: &str, right: &str, input: &[u8]) -> Option<Divergence> {
+    let (left, ..) = record(&compile_module(left, OptLevel::O0), input);
+    let (right, ..) = record(&compile_module(right, OptLevel::O0), input);
+    diff(
+        TraceReader::new(&left[..]).unwrap(),
+        TraceReader::new(&right[..]).unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn replay_reproduces_the_run() {
+    let module = compile_module(",[>+++[>+<-]<-]>>.,.", OptLevel::O0);
+    let (trace, output, cells) = record(&module, &[3, 7]);
+
+    let events: Vec<_> = TraceReader::new(&trace[..])
+        .unwrap()
+        .collect::<io::Result<_>>()
+        .unwrap();
+    assert!(matches!(events[0], Event::Enter(_)));
+    assert!(events.contains(&Event::Input(7)));
+
+    let replay = replay(&trace);
+    assert_eq!(replay.output, output);
+    assert_eq!(replay.input, [3, 7]);
+    assert_eq!(replay.tape, cells[..replay.tape.len()]);
+    assert!(cells[replay.tape.len()..].iter().all(|&c| c == 0));
+}
+
+#[test]
+fn optimization_levels_have_the_same_effects() {
+    let cases: &[(&str, &[u8])] = &[
+        ("++++++++[>++++++++[>+>++<<-]<-]>>.>.", b""),
+        (">,[>,]<[.<]", b"abc"),
+        ("+++[>+++[>+<-]<-]>>[-<+>]<.", b""),
+    ];
+    for &(src, input) in cases {
+        let (unoptimized, ..) = record(&compile_module(src, OptLevel::O0), input);
+        let (optimized, ..) = record(&compile_module(src, OptLevel::O2), input);
+        assert_eq!(
+            diff_end_states(&replay(&unoptimized), &replay(&optimized)),
+            None,
+            "{src}"
+        );
+    }
+}
+
+#[test]
+fn reports_the_first_divergence() {
+    let (a, ..) = record(&compile_module("+.>++.", OptLevel::O0), b"");
+    let (b, ..) = record(&compile_module("+.>+++.", OptLevel::O0), b"");
+    let (c, ..) = record(&compile_module("+.>++.>+", OptLevel::O0), b"");
+    let (d, ..) = record(&compile_module("+.>++.,", OptLevel::O0), b"x");
+    let (a, b, c, d) = (replay(&a), replay(&b), replay(&c), replay(&d));
+
+    assert_eq!(
+        diff_end_states(&a, &b),
+        Some(Divergence::Output {
+            position: 1,
+            left: Some(2),
+            right: Some(3)
+        })
+    );
+    assert_eq!(
+        diff_end_states(&a, &c),
+        Some(Divergence::Cell {
+            cell: 2,
+            left: 0,
+            right: 1,
+            left_after: None,
+            right_after: Some(2)
+        })
+    );
+    assert_eq!(
+        diff_end_states(&d, &a),
+        Some(Divergence::Cell {
+            cell: 1,
+            left: b'x',
+            right: 2,
+            left_after: Some(2),
+            right_after: Some(2)
+        })
+    );
+
+    let (e, ..) = record(&compile_module("++.[-]", OptLevel::O0), b"");
+    let (f, ..) = record(&compile_module("+++.[-]", OptLevel::O0), b"");
+    let (e, f) = (replay(&e), replay(&f));
+    assert!(matches!(
+        diff_end_states(&e, &f),
+        Some(Divergence::Output { position: 0, .. })
+    ));
+    let (g, ..) = record(&compile_module("++[-]", OptLevel::O0), b"");
+    let (h, ..) = record(&compile_module("+++[-]", OptLevel::O0), b"");
+    assert_eq!(
+        diff_end_states(&replay(&g), &replay(&h)),
+        None,
+        "only end states are compared"
+    );
+}
+
+#[test]
+fn diff_walks_both_traces_in_step() {
+    assert_eq!(diff_traces("+.>++.", "+.>++.", b""), None);
+    assert_eq!(
+        diff_traces("+.>++.", "+.>+++.", b""),
+        Some(Divergence::Output {
+            position: 1,
+            left: Some(2),
+            right: Some(3)
+        })
+    );
+    assert_eq!(
+        diff_traces("+.>++.", "+.>++.>+", b""),
+        Some(Divergence::Store {
+            cell: 2,
+            index: 0,
+            left: None,
+            right: Some(1),
+            left_after: None,
+            right_after: Some(2)
+        })
+    );
+    assert_eq!(
+        diff_traces(",[-].", "[-].", b"x"),
+        Some(Divergence::Input { left: 1, right: 0 })
+    );
+}
+
+#[test]
+fn diff_reports_stores_that_reconverge() {
+    let src = |n| format!("{}[>+<-]>[-]<.", "+".repeat(n));
+    assert_eq!(
+        diff_end_states(
+            &replay(&record(&compile_module(&src(3), OptLevel::O0), b"").0),
+            &replay(&record(&compile_module(&src(2), OptLevel::O0), b"").0)
+        ),
+        None
+    );
+    let Some(Divergence::Store {
+        index: 0,
+        left: Some(left),
+        right: Some(right),
+        ..
+    }) = diff_traces(&src(3), &src(2), b"")
+    else {
+        panic!("expected the first store to differ")
+    };
+    assert_ne!(left, right);
+}
+
+#[test]
+fn corrupt_traces_are_rejected() {
+    assert!(TraceReader::new(&b"BFSN\x01"[..]).is_err());
+    let events: Vec<_> = TraceReader::new(&b"BFTR\x01\x09"[..]).unwrap().collect();
+    assert!(events[0].is_err());
+}
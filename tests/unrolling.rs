@@ -0,0 +1,95 @@
+use rustfck::frontend::{
+    expr_tree::{BoundsRange, Instruction, Program},
+    optimize::{apply_optimizations, unroll_constant_loops},
+};
+
+mod common;
+use common::{compile, contains_loop, run};
+
+fn counted(start: u8, body: Vec<Instruction>) -> Program {
+    Program(vec![
+        Instruction::BoundsCheck(BoundsRange {
+            start: 0,
+            length: 3,
+        }),
+        Instruction::Input(1),
+        Instruction::Set(0, start),
+        Instruction::Loop(true, 0, body),
+        Instruction::Output(1),
+        Instruction::Output(2),
+    ])
+}
+
+#[test]
+fn small_loops_are_unrolled() {
+    use Instruction::*;
+    let reference = counted(3, vec![Output(1), Modify(1, 1), Modify(0, -1)]);
+    let mut unrolled = reference.clone();
+    unroll_constant_loops(&mut unrolled, 64);
+
+    assert!(!contains_loop(&unrolled.0));
+    assert_eq!(unrolled.0.iter().filter(|i| **i == Output(1)).count(), 4);
+    assert_eq!(run(&unrolled, b"a"), run(&reference, b"a"));
+}
+
+#[test]
+fn large_linear_loops_become_multiplications() {
+    use Instruction::*;
+    for start in (2..=254).step_by(4) {
+        let reference = counted(start, vec![Modify(1, 3), Modify(0, -2), Set(2, 9)]);
+        let mut unrolled = reference.clone();
+        unroll_constant_loops(&mut unrolled, 4);
+
+        assert!(!contains_loop(&unrolled.0));
+        assert_eq!(run(&unrolled, b"a"), run(&reference, b"a"), "{start}");
+    }
+}
+
+#[test]
+fn large_non_linear_loops_are_kept() {
+    use Instruction::*;
+    let mut program = counted(200, vec![Output(1), Modify(0, -1)]);
+    unroll_constant_loops(&mut program, 64);
+    assert!(contains_loop(&program.0));
+}
+
+#[test]
+fn infinite_loops_are_kept() {
+    use Instruction::*;
+    let mut program = counted(3, vec![Output(1), Modify(0, -2)]);
+    unroll_constant_loops(&mut program, 64);
+    assert!(contains_loop(&program.0));
+}
+
+#[test]
+fn untouched_cells_are_zero() {
+    use Instruction::*;
+    let check = BoundsCheck(BoundsRange {
+        start: 0,
+        length: 4,
+    });
+    let mut program = Program(vec![
+        check.clone(),
+        Set(2, 0),
+        Modify(1, 3),
+        Output(1),
+        Move(1),
+        Set(2, 0),
+    ]);
+    unroll_constant_loops(&mut program, 64);
+    assert_eq!(
+        program,
+        Program(vec![check, Set(1, 3), Output(1), Move(1), Set(2, 0)])
+    );
+}
+
+#[test]
+fn unrolling_in_pipeline_preserves_output() {
+    let src = ",>++++[<.+>-]>++++++[<++++[<+>-]>-]<<.";
+    let reference = compile(src);
+    let mut optimized = reference.clone();
+    apply_optimizations(&mut optimized);
+
+    assert!(!contains_loop(&optimized.0));
+    assert_eq!(run(&optimized, b"A"), run(&reference, b"A"));
+}
@@ -0,0 +1,28 @@
+use rustfck::frontend::{
+    expr_tree::{BoundsRange, Instruction, Program},
+    optimize::remove_dead_verifications,
+};
+
+fn check(start: isize, length: usize) -> Instruction {
+    Instruction::BoundsCheck(BoundsRange { start, length })
+}
+
+#[test]
+fn checks_inside_loops_do_not_cover_later_accesses() {
+    use Instruction::*;
+    let body = vec![check(0, 4), Modify(3, 1), Modify(0, -1)];
+    for block in [Loop(true, 0, body.clone()), If(true, 0, body)] {
+        let mut program = Program(vec![check(0, 1), Input(0), block, check(0, 4), Output(3)]);
+        let before = program.clone();
+        remove_dead_verifications(&mut program);
+        assert_eq!(program, before);
+    }
+}
+
+#[test]
+fn covered_checks_are_removed() {
+    use Instruction::*;
+    let mut program = Program(vec![check(0, 4), Input(0), check(1, 2), Output(2)]);
+    remove_dead_verifications(&mut program);
+    assert_eq!(program, Program(vec![check(0, 4), Input(0), Output(2)]));
+}
@@ -0,0 +1,107 @@
+use rustfck::ir::{
+    builder::Builder,
+    bytecode::{lower, LowerError},
+    instruction::{BinaryOp, LeafExpr, TargetBlock, TestOp},
+    types::Type,
+    Module,
+};
+
+mod common;
+use common::run_both;
+
+#[test]
+fn block_arguments_are_moved_in_parallel() {
+    let mut module = Module::new();
+    let entry = module.add_block();
+    let header = module.add_block();
+    let exit = module.add_block();
+    module.set_entry_block(entry);
+
+    let mut b = Builder::new(&mut module, entry);
+    b.jump(TargetBlock::new(
+        header,
+        vec![1u8.into(), 2u8.into(), 3u8.into()],
+    ));
+
+    b.select_block(header);
+    let x = b.add_parameter(Type::I8);
+    let y = b.add_parameter(Type::I8);
+    let n = b.add_parameter(Type::I8);
+    let next = b.sub(n, 1u8);
+    let more = b.test(TestOp::NotEqual, next, 0u8);
+    let args: Vec<LeafExpr> = vec![y.into(), x.into(), next.into()];
+    b.branch(
+        more,
+        TargetBlock::new(header, args),
+        TargetBlock::new(exit, vec![y.into(), x.into()]),
+    );
+
+    b.select_block(exit);
+    let x = b.add_parameter(Type::I8);
+    let y = b.add_parameter(Type::I8);
+    b.output(x);
+    b.output(y);
+
+    assert_eq!(run_both(&module), [2, 1]);
+}
+
+#[test]
+fn arithmetic_matches_exec() {
+    let mut module = Module::new();
+    let entry = module.add_block();
+    module.set_entry_block(entry);
+
+    let mut b = Builder::new(&mut module, entry);
+    let a = b.set(249u8);
+    let values = [
+        b.add(a, 10u8),
+        b.mul(a, 3u8),
+        b.udiv(a, 7u8),
+        b.idiv(a, 2u8),
+        b.umod(a, 7u8),
+        b.imod(a, 4u8),
+        b.neg(a),
+        b.not(a),
+        b.xor(a, 0x0fu8),
+    ];
+    for v in values {
+        b.output(v);
+    }
+    b.check_bounds(0u64, 1u64);
+    b.store_cell(0u64, 65u8);
+    let loaded = b.load_cell(0u64);
+    b.output(loaded);
+
+    let wide = b.set(5u64);
+    let wide = b.sub(wide, 7u64);
+    let wide = b.udiv(wide, 1u64 << 56);
+    let wrapped = b.test(TestOp::Equal, wide, 255u64);
+    let wrapped = b.not(wrapped);
+    let yes = b.add_block();
+    let no = b.add_block();
+    b.branch(wrapped, no, yes);
+    b.select_block(yes);
+    b.output(b'y');
+    b.select_block(no);
+    b.output(b'n');
+
+    assert_eq!(run_both(&module).last(), Some(&b'y'));
+}
+
+#[test]
+fn undefined_operations_fail_to_lower() {
+    for op in [BinaryOp::IDiv, BinaryOp::IMod] {
+        let mut module = Module::new();
+        let entry = module.add_block();
+        module.set_entry_block(entry);
+        let mut b = Builder::new(&mut module, entry);
+        let quotient = b.binop(op, true, true);
+        let byte = b.zext(quotient, Type::I8);
+        b.output(byte);
+
+        assert_eq!(
+            lower(&module).unwrap_err(),
+            LowerError::Binary(op, Type::I1)
+        );
+    }
+}